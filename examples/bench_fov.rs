@@ -0,0 +1,102 @@
+use specs::prelude::*;
+use rltk::{Point, RGB, RandomNumberGenerator};
+use std::time::Instant;
+use rogueliketutorial::{Map, MapConfig, Position, Viewshed, Player, BitSet, FovAlgorithm, VisibilitySystem};
+
+//Benchmark of the visibility system, run with "cargo run --release --example bench_fov"
+
+//Size of the benchmark's map, and how many viewers are on it
+const BENCH_MAP_SIZE : i32 = 200;
+const BENCH_VIEWERS : usize = 100;
+const BENCH_TURNS : u32 = 200;
+
+//Function to time the visibility system on a large map full of viewers with each field of view algorithm, printing the average cost of a turn and how symmetric the algorithm is
+fn main() {
+    //Build a world with a large, fully lit cave map, so every tile the player could see counts
+    let mut ecs = World::new();
+    let mut vis = VisibilitySystem{};
+    vis.setup(&mut ecs); //Register every storage the visibility system reads, so the benchmark can't fall behind it
+
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let config = MapConfig::default();
+    let mut map = Map::new_map_cellular_automata(1, BENCH_MAP_SIZE, BENCH_MAP_SIZE, &config.cellular_automata, &mut rng);
+    for light in map.light.iter_mut() { *light = RGB::from_f32(1.0, 1.0, 1.0); }
+
+    //Scatter the viewers over the floor, the first of them being the player
+    let floor : Vec<usize> = (0..map.tiles.len()).filter(|idx| map.tiles[*idx].is_walkable()).collect();
+    let mut starts : Vec<Point> = Vec::new();
+    for i in 0..BENCH_VIEWERS {
+        let idx = floor[rng.range(0, floor.len() as i32) as usize];
+        let (x, y) = map.idx_xy(idx);
+        starts.push(Point::new(x, y));
+        let viewer = ecs.create_entity().with(Position{ x, y }).with(Viewshed::new(8));
+        if i == 0 { viewer.with(Player{}).build(); } else { viewer.build(); }
+    }
+
+    //Time the old approach of a fresh field of view from RLTK per viewer, for comparison
+    let start = Instant::now();
+    for _ in 0..BENCH_TURNS {
+        for pos in starts.iter() {
+            let visible = rltk::field_of_view(*pos, 8, &map);
+            std::hint::black_box(visible);
+        }
+    }
+    report("RLTK field_of_view, every viewer", start, starts.len());
+
+    ecs.insert(map);
+    vis.run_now(&ecs); //Warm up, so the viewsheds' buffers are allocated before timing
+
+    //Time turns where every viewer moved, so every viewshed has to be recalculated
+    for algorithm in FovAlgorithm::ALL.iter() {
+        for viewshed in (&mut ecs.write_storage::<Viewshed>()).join() {
+            viewshed.algorithm = *algorithm;
+        }
+        let start = Instant::now();
+        for _ in 0..BENCH_TURNS {
+            for viewshed in (&mut ecs.write_storage::<Viewshed>()).join() {
+                viewshed.dirty = true;
+            }
+            vis.run_now(&ecs);
+        }
+        report(&format!("Visibility system with {:?}, every viewer moved", algorithm), start, BENCH_VIEWERS);
+        println!("    {:.1}% of the floor seen by a viewer can't see it back", asymmetry(&ecs.fetch::<Map>(), &starts, *algorithm) * 100.0);
+    }
+
+    //Time turns where nobody moved, leaving only the work of checking who can see whom
+    let start = Instant::now();
+    for _ in 0..BENCH_TURNS {
+        vis.run_now(&ecs);
+    }
+    report("Visibility system, no viewer moved", start, BENCH_VIEWERS);
+}
+
+//Function to get the fraction of the floor tiles seen from the given positions that can't see the position back
+fn asymmetry(map : &Map, positions : &[Point], algorithm : FovAlgorithm) -> f64 {
+    let field_of_view = algorithm.field_of_view();
+    let visible_from = |pos : Point| {
+        let mut visible = BitSet::new(map.tiles.len());
+        field_of_view.compute(pos, 8, map, &mut |x, y| {
+            if let Some(idx) = map.try_idx(x, y) { visible.insert(idx); }
+        });
+        visible
+    };
+
+    let (mut seen, mut one_sided) = (0, 0);
+    for pos in positions.iter() {
+        let pos_idx = map.try_idx(pos.x, pos.y).expect("Viewer is off the map");
+        for idx in visible_from(*pos).iter().filter(|idx| *idx != pos_idx && map.tiles[*idx].is_walkable()) {
+            seen += 1;
+            let (x, y) = map.idx_xy(idx);
+            if !visible_from(Point::new(x, y)).contains(pos_idx) {
+                one_sided += 1;
+            }
+        }
+    }
+    if seen == 0 { 0.0 } else { one_sided as f64 / seen as f64 }
+}
+
+//Function to print how long a benchmark took per turn
+fn report(name : &str, start : Instant, viewers : usize) {
+    let per_turn = start.elapsed().as_secs_f64() * 1000.0 / BENCH_TURNS as f64;
+    println!("{}: {:.3} ms per turn ({} viewers, {}x{} map, {} turns)", name, per_turn, viewers, BENCH_MAP_SIZE, BENCH_MAP_SIZE, BENCH_TURNS);
+}
@@ -0,0 +1,123 @@
+use std::ops::Index;
+
+//Fixed size set of tile indices stored one bit per tile, so checking, adding and removing a tile is cheap and clearing a whole map is a handful of words
+#[derive(Default, Clone)]
+pub struct BitSet {
+    words : Vec<u64>,
+    len : usize
+}
+
+impl BitSet {
+    //Cunstructor function to create an empty BitSet able to hold the indices 0 to len - 1
+    pub fn new(len : usize) -> BitSet {
+        BitSet{ words : vec![0; (len + 63) / 64], len }
+    }
+
+    //Function to get the number of indices this BitSet can hold
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    //Function to check if this BitSet can't hold any indices
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    //Function to check if an index is in the set
+    pub fn contains(&self, idx : usize) -> bool {
+        idx < self.len && self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    //Function to add an index to the set, returning true if it wasn't already in it
+    pub fn insert(&mut self, idx : usize) -> bool {
+        debug_assert!(idx < self.len, "Index {} is out of range for a BitSet of {}", idx, self.len);
+        let was_set = self.contains(idx);
+        self.words[idx / 64] |= 1 << (idx % 64);
+        !was_set
+    }
+
+    //Function to take an index out of the set
+    pub fn remove(&mut self, idx : usize) {
+        debug_assert!(idx < self.len, "Index {} is out of range for a BitSet of {}", idx, self.len);
+        self.words[idx / 64] &= !(1 << (idx % 64));
+    }
+
+    //Function to take every index out of the set
+    pub fn clear(&mut self) {
+        for word in self.words.iter_mut() { *word = 0; }
+    }
+
+    //Function to iterate over the indices in the set, in order, skipping empty words a whole word at a time
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, word)| {
+            let mut bits = *word;
+            std::iter::from_fn(move || {
+                if bits == 0 { return None; }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1; //Drop the lowest set bit
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+//Allow reading a BitSet like a Vec<bool>, with set[idx]
+impl Index<usize> for BitSet {
+    type Output = bool;
+
+    fn index(&self, idx : usize) -> &bool {
+        if self.contains(idx) { &true } else { &false }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitSet;
+
+    #[test]
+    fn insert_reports_new_indices() {
+        let mut set = BitSet::new(100);
+        assert!(set.insert(5));
+        assert!(!set.insert(5));
+        assert!(set.contains(5));
+        assert!(set[5]);
+        assert!(!set[6]);
+    }
+
+    #[test]
+    fn remove_and_clear() {
+        let mut set = BitSet::new(130);
+        set.insert(0);
+        set.insert(64);
+        set.insert(129);
+        set.remove(64);
+        assert!(set.contains(0) && !set.contains(64) && set.contains(129));
+        set.clear();
+        assert_eq!(set.iter().count(), 0);
+    }
+
+    #[test]
+    fn iter_in_order_across_words() {
+        let mut set = BitSet::new(200);
+        for idx in [199, 3, 64, 63, 128].iter() {
+            set.insert(*idx);
+        }
+        assert_eq!(set.iter().collect::<Vec<usize>>(), vec![3, 63, 64, 128, 199]);
+    }
+
+    #[test]
+    fn out_of_range_is_not_contained() {
+        let set = BitSet::new(10);
+        assert_eq!(set.len(), 10);
+        assert!(!set.contains(10));
+        assert!(!set.contains(1000));
+        assert!(BitSet::new(0).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_out_of_range_panics() {
+        let mut set = BitSet::new(10);
+        set.insert(10);
+    }
+}
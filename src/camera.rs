@@ -1,49 +1,95 @@
 use specs::prelude::*;
-use super::{Map, TileType, Position, Renderable};
+use super::{Map, TileType, Position, Renderable, Name, TileSize, Hidden, Viewshed, ParticleLifetime, can_see, RenderMode, Tileset, TILE_CONSOLE, ASCII_CONSOLE, EFFECTS_CONSOLE};
 use rltk::{Point, Rltk, RGB};
 
-//Whether or not to draw the area outside of the map
-const SHOW_BOUNDARIES : bool = false;
+//Resource holding the camera options that can be changed while the game is running
+#[derive(Default)]
+pub struct CameraSettings {
+    pub clamp_to_edges : bool, //Stop scrolling at the edges of the map instead of always centering on the focus
+    pub show_boundaries : bool, //Draw the area outside of the map
+    pub look_cursor : Option<Point> //When set, the camera follows this cursor instead of the player
+}
 
 //Function to get the bounds of the camera
 pub fn get_screen_bounds(ecs: &World, ctx : &mut Rltk) -> (i32, i32, i32, i32) {
     let player_pos = ecs.fetch::<Point>();
+    let settings = ecs.fetch::<CameraSettings>();
+    let map = ecs.fetch::<Map>();
     let (x_chars, y_chars) = ctx.get_char_size();
 
     let center_x = (x_chars / 2) as i32;
     let center_y = (y_chars / 2) as i32;
 
-    let min_x = player_pos.x - center_x;
+    //Center on the look cursor if there is one, otherwise on the player
+    let focus = settings.look_cursor.unwrap_or(*player_pos);
+    let mut min_x = focus.x - center_x;
+    let mut min_y = focus.y - center_y;
+
+    //Keep the camera inside the map, or center the map if it is smaller than the screen
+    if settings.clamp_to_edges {
+        min_x = clamp_axis(min_x, map.width, x_chars as i32);
+        min_y = clamp_axis(min_y, map.height, y_chars as i32);
+    }
+
     let max_x = min_x + x_chars as i32;
-    let min_y = player_pos.y - center_y;
     let max_y = min_y + y_chars as i32;
 
     (min_x, max_x, min_y, max_y)
 }
 
+//Function to clamp the start of the camera along one axis so the screen stays inside the map
+fn clamp_axis(min : i32, map_size : i32, screen_size : i32) -> i32 {
+    if map_size <= screen_size {
+        (map_size - screen_size) / 2
+    } else {
+        min.max(0).min(map_size - screen_size)
+    }
+}
+
+//Function to convert a position on the screen to a position on the map
+pub fn screen_to_map(ecs: &World, ctx : &mut Rltk, screen_pos : (i32, i32)) -> Point {
+    let (min_x, _max_x, min_y, _max_y) = get_screen_bounds(ecs, ctx);
+    Point::new(screen_pos.0 + min_x, screen_pos.1 + min_y)
+}
+
 //Function to draw the part of the tile map / entities within the camera's bounds
 pub fn render_camera(ecs: &World, ctx : &mut Rltk) {
-    //Get the map from the ECS
-    let map = ecs.fetch::<Map>();
-
     //Calculate the bounds of the camera
     let (min_x, max_x, min_y, max_y) = get_screen_bounds(ecs, ctx);
 
+    //Get the map, camera settings and tileset from the ECS
+    let map = ecs.fetch::<Map>();
+    let settings = ecs.fetch::<CameraSettings>();
+    let tileset = ecs.fetch::<Tileset>();
+    let use_tiles = *ecs.fetch::<RenderMode>() == RenderMode::Tiles;
+
     //Draw the map
-    let map_width = map.width - 1;
-    let map_height = map.height - 1;
+    let viewport_width = max_x - min_x;
+    let viewport_height = max_y - min_y;
 
     let mut y = 0;
     for ty in min_y .. max_y {
         let mut x = 0;
         for tx in min_x .. max_x {
-            if tx >= 0 && tx <= map_width && ty >= 0 && ty <= map_height {
-                let idx = map.xy_idx(tx, ty);
+            if let Some(idx) = map.try_idx(tx, ty) {
                 if map.revealed_tiles[idx] { //Only draw revealed tiles
-                    let (glyph, fg, bg) = get_tile_glyph(idx, &*map);
-                    ctx.set(x, y, fg, bg, glyph); //Draw the tile at its position
+                    let sprite = if use_tiles { tileset.tile_sprite(&*map, tx, ty) } else { None };
+                    if let Some(sprite) = sprite {
+                        let tint = if map.visible_tiles[idx] { map.light_at(idx) } else { RGB::from_f32(0.5, 0.5, 0.5) }; //Dim the sprite if it isn't currently visible
+                        draw_sprite(ctx, x, y, sprite, tint); //Draw the tile's sprite at its position
+                    } else {
+                        let (glyph, fg, bg) = get_tile_glyph(idx, &*map);
+                        ctx.set(x, y, fg, bg, glyph); //Draw the tile at its position
+                    }
+
+                    //Draw what the player remembers of the static entities on tiles they can no longer see, greyed out like the tile
+                    if !map.visible_tiles[idx] {
+                        if let Some(remembered) = map.remembered_entities[idx] {
+                            ctx.set(x, y, remembered.fg.to_greyscale(), RGB::named(rltk::BLACK), remembered.glyph);
+                        }
+                    }
                 }
-            } else if SHOW_BOUNDARIES {
+            } else if settings.show_boundaries {
                 ctx.set(x, y, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), rltk::to_cp437('·')); //Draw an indicator outside of the map bounds if it is enabled
             }
             x += 1;
@@ -54,18 +100,75 @@ pub fn render_camera(ecs: &World, ctx : &mut Rltk) {
     //Draw entities
     let positions = ecs.read_storage::<Position>(); //Get read access to the ECS's Position component storage
     let renderables = ecs.read_storage::<Renderable>(); //Get read access to the ECS's Renderable component storage
-    let data = (&positions, &renderables).join().collect::<Vec<_>>(); //All entities with both a Position and a Renderable component
-
-    for (pos, render) in data.iter() {
-        let idx = map.xy_idx(pos.x, pos.y);
-        if map.visible_tiles[idx] { //Only draw entities on visible tiles
-            let entity_screen_x = pos.x - min_x;
-            let entity_screen_y = pos.y - min_y;
-            if entity_screen_x >= 0 && entity_screen_x <= map_width && entity_screen_y >= 0 && entity_screen_y <= map_height {
-                ctx.set(entity_screen_x, entity_screen_y, render.fg, render.bg, render.glyph); //Draw the entities render properties at its position
+    let names = ecs.read_storage::<Name>(); //Get read access to the ECS's Name component storage, used to look up sprites
+    let sizes = ecs.read_storage::<TileSize>(); //Get read access to the ECS's TileSize component storage, for entities covering more than one tile
+    let hidden = ecs.read_storage::<Hidden>(); //Get read access to the ECS's Hidden component storage
+    let particles = ecs.read_storage::<ParticleLifetime>(); //Get read access to the ECS's ParticleLifetime component storage
+    let entities = ecs.entities();
+    let player_entity = ecs.fetch::<Entity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let telepathic : &[Entity] = viewsheds.get(*player_entity).map(|viewshed| viewshed.telepathic_entities.as_slice()).unwrap_or(&[]); //Monsters the player senses but can't see
+    let mut data = (&entities, &positions, &renderables, names.maybe(), sizes.maybe(), !&particles).join().collect::<Vec<_>>(); //All entities with both a Position and a Renderable component, other than particles
+    data.sort_by(|a, b| b.2.render_order.cmp(&a.2.render_order)); //Draw the highest render order first, so the lowest ends up on top
+
+    for (entity, pos, render, name, size, _particle) in data.iter() {
+        let sensed = telepathic.contains(entity);
+        if hidden.get(*entity).is_some() && !sensed && !can_see(ecs, *player_entity, *entity) { continue; } //Hidden entities are only drawn once the player has noticed them
+
+        let sprite = if use_tiles { name.and_then(|name| tileset.entity_sprite(&name.name)) } else { None };
+        let (width, height) = size.map(|size| (size.x, size.y)).unwrap_or((1, 1));
+
+        //Draw each tile the entity covers separately, so entities partly off the screen, off the map or out of sight still show the rest of themselves
+        for ty in pos.y .. pos.y + height {
+            for tx in pos.x .. pos.x + width {
+                let idx = match map.try_idx(tx, ty) {
+                    Some(idx) => idx,
+                    None => continue //Skip the parts of the entity that are off the map
+                };
+                if !map.visible_tiles[idx] && !sensed { continue; } //Only draw entities on visible tiles, unless they're sensed by telepathy
+
+                let entity_screen_x = tx - min_x;
+                let entity_screen_y = ty - min_y;
+                if entity_screen_x < 0 || entity_screen_x >= viewport_width || entity_screen_y < 0 || entity_screen_y >= viewport_height { continue; } //Clip to the viewport
+
+                if sensed {
+                    //Draw monsters sensed by telepathy in their own colours, so they can't be mistaken for ones in sight
+                    match sprite {
+                        Some(sprite) => draw_sprite(ctx, entity_screen_x, entity_screen_y, sprite, RGB::named(rltk::MAGENTA)),
+                        None => ctx.set(entity_screen_x, entity_screen_y, RGB::named(rltk::MAGENTA), RGB::from_u8(50, 0, 60), render.glyph)
+                    }
+                } else if let Some(sprite) = sprite {
+                    draw_sprite(ctx, entity_screen_x, entity_screen_y, sprite, map.light_at(idx)); //Draw the entity's sprite at its position
+                } else {
+                    ctx.set(entity_screen_x, entity_screen_y, render.fg * map.light_at(idx), render.bg, render.glyph); //Draw the entities render properties at its position, tinted by the light on it
+                }
             }
         }
     }
+
+    //Draw particles on their own layer over the map, fading out as they age
+    ctx.set_active_console(EFFECTS_CONSOLE);
+    for (pos, render, particle) in (&positions, &renderables, &particles).join() {
+        let particle_screen_x = pos.x - min_x;
+        let particle_screen_y = pos.y - min_y;
+        if particle_screen_x >= 0 && particle_screen_x < viewport_width && particle_screen_y >= 0 && particle_screen_y < viewport_height {
+            let remaining = (particle.lifetime_ms / particle.initial_lifetime_ms).max(0.0);
+            ctx.set(particle_screen_x, particle_screen_y, render.fg * remaining, render.bg, render.glyph);
+        }
+    }
+    ctx.set_active_console(ASCII_CONSOLE);
+
+    //Highlight the look cursor
+    if let Some(cursor) = settings.look_cursor {
+        ctx.set_bg(cursor.x - min_x, cursor.y - min_y, RGB::named(rltk::MAGENTA));
+    }
+}
+
+//Function to draw a sprite on the tileset console, tinted with the given colour
+fn draw_sprite(ctx : &mut Rltk, x : i32, y : i32, sprite : rltk::FontCharType, tint : RGB) {
+    ctx.set_active_console(TILE_CONSOLE);
+    ctx.set(x, y, tint, RGB::named(rltk::BLACK), sprite);
+    ctx.set_active_console(ASCII_CONSOLE);
 }
 
 //Function to get the render properties of a tile from its position
@@ -80,17 +183,57 @@ fn get_tile_glyph(idx: usize, map : &Map) -> (rltk::FontCharType, RGB, RGB) {
             fg = RGB::from_u8(80, 55, 10);
         }
         TileType::Wall => {
-            glyph = rltk::to_cp437('#');
+            glyph = wall_glyph(map.wall_masks[idx]);
             fg = RGB::from_u8(160, 110, 20);
         }
         TileType::DownStairs => {
             glyph = rltk::to_cp437('>');
             fg = RGB::from_u8(255, 255, 255);
         }
+        TileType::ShallowWater => {
+            glyph = rltk::to_cp437('~');
+            fg = RGB::from_u8(60, 140, 220);
+        }
+        TileType::DeepWater => {
+            glyph = rltk::to_cp437('≈');
+            fg = RGB::from_u8(20, 60, 200);
+        }
+        TileType::Lava => {
+            glyph = rltk::to_cp437('≈');
+            fg = RGB::from_u8(255, 90, 0);
+        }
+        TileType::Bridge => {
+            glyph = rltk::to_cp437('=');
+            fg = RGB::from_u8(140, 90, 40);
+        }
+        TileType::Rubble => {
+            glyph = rltk::to_cp437(';');
+            fg = RGB::from_u8(120, 110, 90);
+        }
     }
     if !map.visible_tiles[idx] { //Grey out revealed tiles that are not currently visible by the player
         fg = fg.to_greyscale();
+    } else { //Tint visible tiles by the light falling on them
+        fg = fg * map.light_at(idx);
     }
 
     (glyph, fg, bg)
 }
+
+//Function to get the box drawing glyph for a wall from the bitmask of the revealed walls around it
+fn wall_glyph(mask : u8) -> rltk::FontCharType {
+    match mask {
+        0 => 9, //Pillar, no walls around it
+        1 | 2 | 3 => 186, //Walls only to the north and/or south
+        4 | 8 | 12 => 205, //Walls only to the west and/or east
+        5 => 188, //North and west
+        6 => 187, //South and west
+        7 => 185, //North, south and west
+        9 => 200, //North and east
+        10 => 201, //South and east
+        11 => 204, //North, south and east
+        13 => 202, //North, west and east
+        14 => 203, //South, west and east
+        _ => 206 //All four directions
+    }
+}
@@ -0,0 +1,25 @@
+use specs::prelude::*;
+use serde::{Serialize, Deserialize};
+use super::{RunState, try_move_player, attempt_interact, attempt_descend};
+
+//Game actions that input sources (keyboard, mouse, replays, scripts or AI) produce for the game to perform
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
+pub enum Command {
+    Move { dx : i32, dy : i32 },
+    Wait,
+    Interact,
+    Descend
+}
+
+//Function to perform a command for the player, returning the run state that follows it
+pub fn execute_command(ecs: &mut World, command : Command) -> RunState {
+    match command {
+        Command::Move{ dx, dy } => {
+            //Bumping in to a wall or the edge of the map doesn't take a turn
+            if try_move_player(dx, dy, ecs) { RunState::Running } else { RunState::Paused }
+        }
+        Command::Wait => RunState::Running, //Skip the turn
+        Command::Interact => attempt_interact(ecs),
+        Command::Descend => attempt_descend(ecs)
+    }
+}
@@ -1,6 +1,7 @@
 use specs::prelude::*;
 use specs_derive::*;
 use rltk::{RGB};
+use super::{BitSet, FovAlgorithm};
 
 #[derive(Component)]
 pub struct Position {
@@ -13,6 +14,7 @@ pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: RGB,
     pub bg: RGB,
+    pub render_order : i32 //Entities with a lower render order are drawn on top of those sharing their tile
 }
 
 #[derive(Component, Debug)]
@@ -21,6 +23,92 @@ pub struct Player {}
 #[derive(Component)]
 pub struct Viewshed {
     pub visible_tiles : Vec<rltk::Point>,
+    pub visible : BitSet, //The same tiles as visible_tiles, by map index, for quick lookups
     pub range : i32,
-    pub dirty : bool
+    pub algorithm : FovAlgorithm, //How this entity works out what it can see
+    pub dirty : bool,
+    pub visible_entities : Vec<Entity>, //Entities this one could see when visibility was last run
+    pub telepathic_entities : Vec<Entity> //Monsters this one can sense with telepathy but not see
 }
+
+impl Viewshed {
+    //Cunstructor function to create a new Viewshed with the given range, which will be calculated on the next turn with shadowcasting
+    pub fn new(range : i32) -> Viewshed {
+        Viewshed::with_algorithm(range, FovAlgorithm::Shadowcasting)
+    }
+
+    //Cunstructor function to create a new Viewshed with the given range and field of view algorithm
+    pub fn with_algorithm(range : i32, algorithm : FovAlgorithm) -> Viewshed {
+        Viewshed{ visible_tiles : Vec::new(), visible : BitSet::default(), range, algorithm, dirty : true, visible_entities : Vec::new(), telepathic_entities : Vec::new() }
+    }
+}
+
+#[derive(Component)]
+pub struct Name {
+    pub name : String
+}
+
+#[derive(Component)]
+pub struct LightSource {
+    pub radius : i32,
+    pub color : RGB
+}
+
+#[derive(Component)]
+pub struct ParticleLifetime {
+    pub lifetime_ms : f32,
+    pub initial_lifetime_ms : f32
+}
+
+#[derive(Component)]
+pub struct Health {
+    pub hp : i32,
+    pub max_hp : i32
+}
+
+#[derive(Component)]
+pub struct TileSize {
+    pub x : i32,
+    pub y : i32
+}
+
+#[derive(Component)]
+pub struct Memorable {}
+
+#[derive(Component, Debug)]
+pub struct Monster {}
+
+#[derive(Component)]
+pub struct Hidden {
+    pub detection_range : i32 //How close a viewer has to be to notice this entity
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ShrineEffect {
+    MagicMapping, Telepathy, Amnesia
+}
+
+#[derive(Component)]
+pub struct Shrine {
+    pub effect : ShrineEffect
+}
+
+#[derive(Component)]
+pub struct Telepathy {
+    pub range : i32,
+    pub turns : i32 //Turns left before it wears off
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum HungerState {
+    WellFed, Normal, Hungry, Starving
+}
+
+#[derive(Component)]
+pub struct HungerClock {
+    pub state : HungerState,
+    pub duration : i32 //Turns left before the next state is reached
+}
+
+#[derive(Component)]
+pub struct ProvidesFood {}
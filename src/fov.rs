@@ -0,0 +1,494 @@
+use rltk::Point;
+use super::{Map, Viewshed, BitSet};
+
+//Field of view algorithms an entity can see with
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum FovAlgorithm {
+    Shadowcasting, //Walls cast square shadows; symmetric in open rooms, quick
+    Permissive, //A tile is seen if any line between the two tiles is clear; symmetric and sees around corners, slower
+    DiamondWalls, //Shadowcasting where walls only cast the shadow of the diamond inside their tile, so pillars hide less
+    Raycasting //Lines out to the edge of the range; simple, but leaves gaps and isn't symmetric
+}
+
+impl FovAlgorithm {
+    pub const ALL : [FovAlgorithm; 4] = [FovAlgorithm::Shadowcasting, FovAlgorithm::Permissive, FovAlgorithm::DiamondWalls, FovAlgorithm::Raycasting];
+
+    //Function to get the implementation of this algorithm
+    pub fn field_of_view(self) -> &'static dyn FieldOfView {
+        match self {
+            FovAlgorithm::Shadowcasting => &Shadowcasting{ diamond_walls : false },
+            FovAlgorithm::Permissive => &Permissive{},
+            FovAlgorithm::DiamondWalls => &Shadowcasting{ diamond_walls : true },
+            FovAlgorithm::Raycasting => &Raycasting{}
+        }
+    }
+}
+
+//A way of working out which tiles can be seen from a position
+pub trait FieldOfView {
+    //Function to find every tile within range of the origin that can be seen from it, calling mark once or more for each
+    fn compute(&self, origin : Point, range : i32, map : &Map, mark : &mut dyn FnMut(i32, i32));
+}
+
+//Function to recalculate a viewshed from a position with its algorithm, reusing its buffers and clearing only the tiles that were visible before
+pub fn update_viewshed(viewshed : &mut Viewshed, origin : Point, map : &Map) {
+    let tile_count = (map.width * map.height) as usize;
+    if viewshed.visible.len() != tile_count {
+        viewshed.visible = BitSet::new(tile_count); //The viewer is on a new map
+        viewshed.visible_tiles.clear();
+    }
+    for p in viewshed.visible_tiles.iter() {
+        if let Some(idx) = map.try_idx(p.x, p.y) {
+            viewshed.visible.remove(idx);
+        }
+    }
+    viewshed.visible_tiles.clear();
+
+    let visible = &mut viewshed.visible;
+    let visible_tiles = &mut viewshed.visible_tiles;
+    viewshed.algorithm.field_of_view().compute(origin, viewshed.range, map, &mut |x, y| {
+        if let Some(idx) = map.try_idx(x, y) {
+            if visible.insert(idx) {
+                visible_tiles.push(Point::new(x, y));
+            }
+        }
+    });
+}
+
+//Function to check if the tile at a position blocks sight, counting the area off the map as blocking
+fn blocks_sight(map : &Map, x : i32, y : i32) -> bool {
+    match map.try_idx(x, y) {
+        Some(idx) => map.tiles[idx].is_opaque(),
+        None => true
+    }
+}
+
+//Transforms from the coordinates of the first octant to each of the eight octants around the viewer: map x = col * xx + row * xy, map y = col * yx + row * yy
+const OCTANTS : [[i32; 4]; 8] = [
+    [1, 0, 0, 1], [0, 1, 1, 0], [0, -1, 1, 0], [-1, 0, 0, 1],
+    [-1, 0, 0, -1], [0, -1, -1, 0], [0, 1, -1, 0], [1, 0, 0, -1]
+];
+
+//Recursive shadowcasting, scanning each octant around the viewer row by row and skipping the shadows behind walls
+struct Shadowcasting {
+    diamond_walls : bool //Walls only block the diamond inscribed in their tile, rather than the whole square
+}
+
+impl FieldOfView for Shadowcasting {
+    fn compute(&self, origin : Point, range : i32, map : &Map, mark : &mut dyn FnMut(i32, i32)) {
+        if !map.in_bounds(origin.x, origin.y) { return; }
+        mark(origin.x, origin.y);
+        for transform in OCTANTS.iter() {
+            let octant = Octant{ origin, range, transform : *transform, diamond_walls : self.diamond_walls };
+            octant.cast(map, 1, 1.0, 0.0, mark);
+        }
+    }
+}
+
+//One of the eight wedges around a viewer that shadowcasting scans, row by row outwards
+struct Octant {
+    origin : Point,
+    range : i32,
+    transform : [i32; 4],
+    diamond_walls : bool
+}
+
+impl Octant {
+    //Function to scan the rows of the octant from the given row outwards, between the start and end slopes, recursing in to the gaps between walls
+    fn cast(&self, map : &Map, first_row : i32, mut start : f32, end : f32, mark : &mut dyn FnMut(i32, i32)) {
+        if start < end { return; }
+        let [xx, xy, yx, yy] = self.transform;
+        let range_squared = self.range * self.range;
+        let mut next_start = start;
+
+        for row in first_row ..= self.range {
+            let mut blocked = false;
+            for col in -row ..= 0 {
+                let (dx, dy) = (col, -row);
+                let x = self.origin.x + dx * xx + dy * xy;
+                let y = self.origin.y + dx * yx + dy * yy;
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope { continue; }
+                if end > left_slope { break; }
+
+                //Tiles off the map block sight, but aren't seen
+                if map.in_bounds(x, y) && dx * dx + dy * dy <= range_squared {
+                    mark(x, y);
+                }
+                let opaque = blocks_sight(map, x, y);
+
+                //The slopes of the edges of the shadow a wall here would cast
+                let (wall_left_slope, wall_right_slope) = if self.diamond_walls {
+                    ((dx as f32 - 0.5) / dy as f32, (dx as f32 + 0.5) / dy as f32)
+                } else {
+                    (left_slope, right_slope)
+                };
+
+                if blocked {
+                    if opaque {
+                        next_start = wall_right_slope; //Still in the shadow of the wall
+                    } else {
+                        blocked = false; //Out the other side of the wall
+                        start = next_start;
+                    }
+                } else if opaque && row < self.range {
+                    //A wall starts here, so scan the gap before it and carry on past it
+                    blocked = true;
+                    self.cast(map, row + 1, start, wall_left_slope, mark);
+                    next_start = wall_right_slope;
+                }
+            }
+            if blocked { break; }
+        }
+    }
+}
+
+//Precise permissive field of view: a tile is seen if any line from any part of the viewer's tile reaches any part of it without passing through a wall, which makes it symmetric
+struct Permissive {}
+
+impl FieldOfView for Permissive {
+    fn compute(&self, origin : Point, range : i32, map : &Map, mark : &mut dyn FnMut(i32, i32)) {
+        if !map.in_bounds(origin.x, origin.y) { return; }
+        mark(origin.x, origin.y);
+
+        //Scan each quadrant around the viewer out to the range or the edge of the map, whichever is closer
+        let (left, right) = (i32::min(origin.x, range), i32::min(map.width - origin.x - 1, range));
+        let (up, down) = (i32::min(origin.y, range), i32::min(map.height - origin.y - 1, range));
+        let quadrants = [((1, 1), (right, down)), ((1, -1), (right, up)), ((-1, -1), (left, up)), ((-1, 1), (left, down))];
+        for (direction, extent) in quadrants.iter() {
+            let quadrant = PermissiveQuadrant{ origin, range, direction : *direction, extent : *extent };
+            quadrant.scan(map, mark);
+        }
+    }
+}
+
+//Line between two tile corners in a quadrant's coordinates, where the viewer's tile covers (0, 0) to (1, 1)
+#[derive(Copy, Clone)]
+struct SightLine {
+    near : (i32, i32),
+    far : (i32, i32)
+}
+
+impl SightLine {
+    //Function to get which side of the line a point is on: positive below it, negative above it and zero on it
+    fn relative_slope(&self, point : (i32, i32)) -> i32 {
+        (self.far.1 - self.near.1) * (self.far.0 - point.0) - (self.far.1 - point.1) * (self.far.0 - self.near.0)
+    }
+
+    fn is_below(&self, point : (i32, i32)) -> bool { self.relative_slope(point) > 0 }
+    fn is_below_or_contains(&self, point : (i32, i32)) -> bool { self.relative_slope(point) >= 0 }
+    fn is_above(&self, point : (i32, i32)) -> bool { self.relative_slope(point) < 0 }
+    fn is_above_or_contains(&self, point : (i32, i32)) -> bool { self.relative_slope(point) <= 0 }
+    fn contains(&self, point : (i32, i32)) -> bool { self.relative_slope(point) == 0 }
+
+    //Function to check if another line lies along this one
+    fn is_collinear(&self, other : &SightLine) -> bool {
+        self.contains(other.near) && self.contains(other.far)
+    }
+}
+
+//Corner of a wall that a view's edge had to bend around, linked to the corner it bent around before
+struct Bump {
+    point : (i32, i32),
+    parent : Option<usize>
+}
+
+//Wedge of a quadrant that can still be seen through, between a shallow and a steep edge
+#[derive(Copy, Clone)]
+struct View {
+    shallow_line : SightLine,
+    steep_line : SightLine,
+    shallow_bump : Option<usize>,
+    steep_bump : Option<usize>
+}
+
+//One of the four quadrants around a viewer that permissive field of view scans, diagonal by diagonal outwards
+struct PermissiveQuadrant {
+    origin : Point,
+    range : i32,
+    direction : (i32, i32),
+    extent : (i32, i32)
+}
+
+impl PermissiveQuadrant {
+    //Function to scan the quadrant, narrowing and splitting the views through it as walls are found
+    fn scan(&self, map : &Map, mark : &mut dyn FnMut(i32, i32)) {
+        let (extent_x, extent_y) = self.extent;
+        let mut bumps : Vec<Bump> = Vec::new();
+        let mut views = vec![View{
+            shallow_line : SightLine{ near : (0, 1), far : (extent_x, 0) },
+            steep_line : SightLine{ near : (1, 0), far : (0, extent_y) },
+            shallow_bump : None,
+            steep_bump : None
+        }];
+
+        for i in 1 ..= extent_x + extent_y {
+            if views.is_empty() { break; }
+            let mut view_idx = 0;
+            for j in i32::max(0, i - extent_x) ..= i32::min(i, extent_y) {
+                if view_idx >= views.len() { break; }
+                view_idx = self.visit(map, (i - j, j), view_idx, &mut views, &mut bumps, mark);
+            }
+        }
+    }
+
+    //Function to see a tile of the quadrant if a view reaches it, and if it's a wall narrow or split that view, returning the view to carry on from
+    fn visit(&self, map : &Map, (x, y) : (i32, i32), mut view_idx : usize, views : &mut Vec<View>, bumps : &mut Vec<Bump>, mark : &mut dyn FnMut(i32, i32)) -> usize {
+        let top_left = (x, y + 1);
+        let bottom_right = (x + 1, y);
+
+        //Skip the views the tile is steeper than, and stop if the tile is shallower than the next one
+        while view_idx < views.len() && views[view_idx].steep_line.is_below_or_contains(bottom_right) {
+            view_idx += 1;
+        }
+        if view_idx == views.len() || views[view_idx].shallow_line.is_above_or_contains(top_left) { return view_idx; }
+
+        let map_x = self.origin.x + x * self.direction.0;
+        let map_y = self.origin.y + y * self.direction.1;
+        if x * x + y * y <= self.range * self.range {
+            mark(map_x, map_y);
+        }
+        if !blocks_sight(map, map_x, map_y) { return view_idx; }
+
+        let view = views[view_idx];
+        if view.shallow_line.is_above(bottom_right) && view.steep_line.is_below(top_left) {
+            //The wall fills the view
+            views.remove(view_idx);
+        } else if view.shallow_line.is_above(bottom_right) {
+            //The wall cuts in to the shallow edge of the view
+            add_shallow_bump(&mut views[view_idx], top_left, bumps);
+            check_view(views, view_idx);
+        } else if view.steep_line.is_below(top_left) {
+            //The wall cuts in to the steep edge of the view
+            add_steep_bump(&mut views[view_idx], bottom_right, bumps);
+            check_view(views, view_idx);
+        } else {
+            //The wall is in the middle of the view, so split it in to the part shallower than the wall and the part steeper than it
+            views.insert(view_idx, view);
+            add_steep_bump(&mut views[view_idx], bottom_right, bumps);
+            if check_view(views, view_idx) { view_idx += 1; }
+            add_shallow_bump(&mut views[view_idx], top_left, bumps);
+            check_view(views, view_idx);
+        }
+        view_idx
+    }
+}
+
+//Function to bend the shallow edge of a view round the corner of a wall, keeping it clear of the corners the steep edge bent round
+fn add_shallow_bump(view : &mut View, point : (i32, i32), bumps : &mut Vec<Bump>) {
+    view.shallow_line.far = point;
+    bumps.push(Bump{ point, parent : view.shallow_bump });
+    view.shallow_bump = Some(bumps.len() - 1);
+
+    let mut bump = view.steep_bump;
+    while let Some(idx) = bump {
+        if view.shallow_line.is_above(bumps[idx].point) {
+            view.shallow_line.near = bumps[idx].point;
+        }
+        bump = bumps[idx].parent;
+    }
+}
+
+//Function to bend the steep edge of a view round the corner of a wall, keeping it clear of the corners the shallow edge bent round
+fn add_steep_bump(view : &mut View, point : (i32, i32), bumps : &mut Vec<Bump>) {
+    view.steep_line.far = point;
+    bumps.push(Bump{ point, parent : view.steep_bump });
+    view.steep_bump = Some(bumps.len() - 1);
+
+    let mut bump = view.shallow_bump;
+    while let Some(idx) = bump {
+        if view.steep_line.is_below(bumps[idx].point) {
+            view.steep_line.near = bumps[idx].point;
+        }
+        bump = bumps[idx].parent;
+    }
+}
+
+//Function to drop a view that has closed down to a single line through a corner of the viewer's tile, returning false if it was dropped
+fn check_view(views : &mut Vec<View>, view_idx : usize) -> bool {
+    let view = views[view_idx];
+    if view.shallow_line.is_collinear(&view.steep_line) && (view.shallow_line.contains((0, 1)) || view.shallow_line.contains((1, 0))) {
+        views.remove(view_idx);
+        false
+    } else {
+        true
+    }
+}
+
+//Raycasting, following a Bresenham line from the viewer to every tile on the edge of its range until something blocks it
+struct Raycasting {}
+
+impl FieldOfView for Raycasting {
+    fn compute(&self, origin : Point, range : i32, map : &Map, mark : &mut dyn FnMut(i32, i32)) {
+        if !map.in_bounds(origin.x, origin.y) { return; }
+        mark(origin.x, origin.y);
+
+        let mut edge : Vec<Point> = Vec::new();
+        for i in -range ..= range {
+            edge.push(Point::new(origin.x + i, origin.y - range));
+            edge.push(Point::new(origin.x + i, origin.y + range));
+            edge.push(Point::new(origin.x - range, origin.y + i));
+            edge.push(Point::new(origin.x + range, origin.y + i));
+        }
+
+        for end in edge.iter() {
+            cast_ray(origin, *end, &mut |x, y| {
+                let (dx, dy) = (x - origin.x, y - origin.y);
+                if !map.in_bounds(x, y) || dx * dx + dy * dy > range * range { return false; }
+                mark(x, y);
+                !blocks_sight(map, x, y) //Walls are seen, but stop the ray
+            });
+        }
+    }
+}
+
+//Function to walk a Bresenham line from one point to another, not including the start, for as long as visit returns true
+fn cast_ray(from : Point, to : Point, visit : &mut dyn FnMut(i32, i32) -> bool) {
+    let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+    let (step_x, step_y) = (if from.x < to.x { 1 } else { -1 }, if from.y < to.y { 1 } else { -1 });
+    let (mut x, mut y) = (from.x, from.y);
+    let mut error = dx + dy;
+    while x != to.x || y != to.y {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        if !visit(x, y) { return; }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TileType;
+
+    //Room with two pillars, looked at from near its west wall
+    const ROOM : [&str; 7] = [
+        "###########",
+        "#.........#",
+        "#...#.....#",
+        "#.........#",
+        "#......#..#",
+        "#.........#",
+        "###########"
+    ];
+
+    //Function to build a map from rows of text, where '#' is a wall and anything else is floor
+    fn map_from_rows(rows : &[&str]) -> Map {
+        let mut map = Map::new(1, rows[0].len() as i32, rows.len() as i32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                if tile != '#' {
+                    let idx = map.try_idx(x as i32, y as i32).expect("Row is wider than the first row");
+                    map.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+        map
+    }
+
+    //Function to get which tiles an algorithm sees from a position
+    fn visible_from(map : &Map, algorithm : FovAlgorithm, origin : Point, range : i32) -> BitSet {
+        let mut visible = BitSet::new(map.tiles.len());
+        algorithm.field_of_view().compute(origin, range, map, &mut |x, y| {
+            visible.insert(map.try_idx(x, y).expect("Marked a tile off the map"));
+        });
+        visible
+    }
+
+    //Function to draw the tiles an algorithm sees, with '@' for the viewer, '#' and '.' for seen walls and floor and ' ' for tiles it can't see
+    fn draw_visible(rows : &[&str], algorithm : FovAlgorithm, origin : Point, range : i32) -> Vec<String> {
+        let map = map_from_rows(rows);
+        let visible = visible_from(&map, algorithm, origin, range);
+        (0..map.height).map(|y| (0..map.width).map(|x| {
+            let idx = map.try_idx(x, y).unwrap();
+            if (x, y) == (origin.x, origin.y) { '@' }
+            else if !visible[idx] { ' ' }
+            else if map.tiles[idx] == TileType::Wall { '#' }
+            else { '.' }
+        }).collect()).collect()
+    }
+
+    #[test]
+    fn shadowcasting_visible_set() {
+        assert_eq!(draw_visible(&ROOM, FovAlgorithm::Shadowcasting, Point::new(2, 3), 8), vec![
+            "#######    ",
+            "#.....   . ",
+            "#...#..... ",
+            "#.@.......#",
+            "#......#.. ",
+            "#......... ",
+            "########## "
+        ]);
+    }
+
+    #[test]
+    fn permissive_visible_set() {
+        assert_eq!(draw_visible(&ROOM, FovAlgorithm::Permissive, Point::new(2, 3), 8), vec![
+            "######   # ",
+            "#..... ... ",
+            "#...#..... ",
+            "#.@.......#",
+            "#......#.. ",
+            "#......... ",
+            "########## "
+        ]);
+    }
+
+    #[test]
+    fn diamond_walls_visible_set() {
+        assert_eq!(draw_visible(&ROOM, FovAlgorithm::DiamondWalls, Point::new(2, 3), 8), vec![
+            "#######    ",
+            "#.....  .. ",
+            "#...#..... ",
+            "#.@.......#",
+            "#......#.. ",
+            "#......... ",
+            "########## "
+        ]);
+    }
+
+    #[test]
+    fn raycasting_visible_set() {
+        assert_eq!(draw_visible(&ROOM, FovAlgorithm::Raycasting, Point::new(2, 3), 8), vec![
+            "#######    ",
+            "#.....     ",
+            "#...# .... ",
+            "#.@.......#",
+            "#......#   ",
+            "#........  ",
+            "######## # "
+        ]);
+    }
+
+    #[test]
+    fn permissive_is_symmetric() {
+        let map = map_from_rows(&[
+            "###############",
+            "#....#........#",
+            "#.#......##...#",
+            "#....#.....#..#",
+            "##.......#....#",
+            "#...#.#.....#.#",
+            "#.........#...#",
+            "###############"
+        ]);
+        let floor : Vec<Point> = (0..map.tiles.len())
+            .filter(|idx| map.tiles[*idx] == TileType::Floor)
+            .map(|idx| Point::new(idx as i32 % map.width, idx as i32 / map.width))
+            .collect();
+        let visible : Vec<BitSet> = floor.iter().map(|pos| visible_from(&map, FovAlgorithm::Permissive, *pos, 8)).collect();
+        for (i, from) in floor.iter().enumerate() {
+            for (j, to) in floor.iter().enumerate() {
+                let (from_idx, to_idx) = (map.try_idx(from.x, from.y).unwrap(), map.try_idx(to.x, to.y).unwrap());
+                assert_eq!(visible[i][to_idx], visible[j][from_idx], "{:?} and {:?} don't see each other the same way", from, to);
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//Resource holding the messages shown to the player, oldest first
+#[derive(Default)]
+pub struct GameLog {
+    pub entries : Vec<String>
+}
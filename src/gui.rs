@@ -0,0 +1,157 @@
+use specs::prelude::*;
+use rltk::{Rltk, RGB, VirtualKeyCode};
+use super::{Map, Position, Name, TileSize, Hidden, Monster, Player, Health, HungerClock, HungerState, GameLog, TurnCounter, ReplayRecorder, camera, can_see, seen_by};
+
+//Options on the main menu
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuSelection { NewGame, Continue, Quit }
+
+//Result of a frame of the main menu: either the highlighted option, or the option that was chosen
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult { NoSelection { selected : MainMenuSelection }, Selected { selected : MainMenuSelection } }
+
+//Function to draw the HUD with the current map depth, the player's health and hunger and the latest message
+pub fn draw_ui(ecs: &World, ctx : &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let depth = format!("Depth: {}", map.depth);
+    ctx.print_color(1, 48, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &depth);
+
+    let players = ecs.read_storage::<Player>();
+    let healths = ecs.read_storage::<Health>();
+    for (_player, health) in (&players, &healths).join() {
+        let hp = format!("HP: {} / {}", health.hp, health.max_hp);
+        ctx.print_color(14, 48, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &hp);
+    }
+
+    //Show how hungry the player is
+    let clocks = ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &clocks).join() {
+        let (status, color) = match clock.state {
+            HungerState::WellFed => ("Well Fed", RGB::named(rltk::GREEN)),
+            HungerState::Normal => ("Normal", RGB::named(rltk::WHITE)),
+            HungerState::Hungry => ("Hungry", RGB::named(rltk::ORANGE)),
+            HungerState::Starving => ("Starving", RGB::named(rltk::RED))
+        };
+        ctx.print_color(46, 48, color, RGB::named(rltk::BLACK), status);
+    }
+
+    //Warn the player when monsters can see them
+    let player_entity = ecs.fetch::<Entity>();
+    let monsters = ecs.read_storage::<Monster>();
+    let watchers = seen_by(ecs, *player_entity).iter().filter(|entity| monsters.get(**entity).is_some()).count();
+    if watchers > 0 {
+        let watched = format!("Watched by {}", watchers);
+        ctx.print_color(30, 48, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), &watched);
+    }
+
+    let log = ecs.fetch::<GameLog>();
+    if let Some(message) = log.entries.last() {
+        ctx.print_color(1, 49, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), message);
+    }
+}
+
+//Function to draw a tooltip describing the terrain and entities under the mouse, if it is over a visible tile
+pub fn draw_tooltip(ecs: &World, ctx : &mut Rltk) {
+    let mouse_pos = ctx.mouse_pos();
+    draw_tooltip_at(ecs, ctx, mouse_pos);
+}
+
+//Function to draw a tooltip describing the terrain and entities at a position on the screen, if it is over a visible tile
+pub fn draw_tooltip_at(ecs: &World, ctx : &mut Rltk, screen_pos : (i32, i32)) {
+    let map_pos = camera::screen_to_map(ecs, ctx, screen_pos);
+    let map = ecs.fetch::<Map>();
+    let idx = match map.try_idx(map_pos.x, map_pos.y) {
+        Some(idx) => idx,
+        None => return
+    };
+    if !map.visible_tiles[idx] { return; } //Only describe tiles the player can currently see
+
+    //Describe the terrain, then every named entity standing on it
+    let mut tooltip : Vec<String> = vec![map.tiles[idx].name().to_string()];
+    let names = ecs.read_storage::<Name>();
+    let positions = ecs.read_storage::<Position>();
+    let sizes = ecs.read_storage::<TileSize>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let monsters = ecs.read_storage::<Monster>();
+    let entities = ecs.entities();
+    let player_entity = ecs.fetch::<Entity>();
+    for (entity, name, pos, size) in (&entities, &names, &positions, sizes.maybe()).join() {
+        if hidden.get(entity).is_some() && !can_see(ecs, *player_entity, entity) { continue; } //Don't give away hidden entities the player hasn't noticed
+        let (width, height) = size.map(|size| (size.x, size.y)).unwrap_or((1, 1));
+        if map_pos.x >= pos.x && map_pos.x < pos.x + width && map_pos.y >= pos.y && map_pos.y < pos.y + height {
+            if monsters.get(entity).is_some() && can_see(ecs, entity, *player_entity) {
+                tooltip.push(format!("{} (watching you)", name.name));
+            } else {
+                tooltip.push(name.name.to_string());
+            }
+        }
+    }
+
+    //Draw the tooltip next to the position, flipping it to the left when it would run off the right of the screen
+    let (screen_width, _screen_height) = ctx.get_char_size();
+    let width = tooltip.iter().map(|line| line.len() as i32).max().unwrap_or(0) + 2;
+    let x = if screen_pos.0 + width + 1 < screen_width as i32 { screen_pos.0 + 1 } else { screen_pos.0 - width };
+    let fg = RGB::named(rltk::WHITE);
+    let bg = RGB::from_u8(60, 60, 60);
+    for (i, line) in tooltip.iter().enumerate() {
+        let y = screen_pos.1 + i as i32;
+        ctx.print_color(x, y, fg, bg, &format!(" {:<width$} ", line, width = width as usize - 2));
+    }
+}
+
+//Function to draw the main menu and read input for it, moving the highlight between the available options
+pub fn main_menu(ctx : &mut Rltk, selected : MainMenuSelection, can_continue : bool) -> MainMenuResult {
+    let mut options = vec![(MainMenuSelection::NewGame, "New Game")];
+    if can_continue { options.push((MainMenuSelection::Continue, "Continue")); }
+    options.push((MainMenuSelection::Quit, "Quit"));
+
+    ctx.print_color_centered(15, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Roguelike Tutorial");
+    for (i, (option, label)) in options.iter().enumerate() {
+        let fg = if *option == selected { RGB::named(rltk::MAGENTA) } else { RGB::named(rltk::WHITE) };
+        ctx.print_color_centered(24 + i as i32 * 2, fg, RGB::named(rltk::BLACK), label);
+    }
+
+    //Move the highlight up and down the options, wrapping around at either end
+    let current = options.iter().position(|(option, _label)| *option == selected).unwrap_or(0);
+    match ctx.key {
+        None => MainMenuResult::NoSelection{ selected },
+        Some(VirtualKeyCode::Escape) => MainMenuResult::NoSelection{ selected : MainMenuSelection::Quit },
+        Some(VirtualKeyCode::Up) | Some(VirtualKeyCode::W) | Some(VirtualKeyCode::K) => {
+            MainMenuResult::NoSelection{ selected : options[(current + options.len() - 1) % options.len()].0 }
+        }
+        Some(VirtualKeyCode::Down) | Some(VirtualKeyCode::S) | Some(VirtualKeyCode::J) => {
+            MainMenuResult::NoSelection{ selected : options[(current + 1) % options.len()].0 }
+        }
+        Some(VirtualKeyCode::Return) => MainMenuResult::Selected{ selected : options[current].0 },
+        _ => MainMenuResult::NoSelection{ selected }
+    }
+}
+
+//Function to draw a summary of the run that just ended, returning true once the player presses a key to go back to the main menu
+fn run_summary(ecs: &World, ctx : &mut Rltk, title : &str, title_color : RGB) -> bool {
+    let depth = ecs.fetch::<Map>().depth;
+    let turns = ecs.fetch::<TurnCounter>().turn;
+    let seed = ecs.fetch::<ReplayRecorder>().replay.seed;
+    let log = ecs.fetch::<GameLog>();
+
+    ctx.print_color_centered(15, title_color, RGB::named(rltk::BLACK), title);
+    if let Some(message) = log.entries.last() {
+        ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), message);
+    }
+    ctx.print_color_centered(21, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Deepest level reached: {}", depth));
+    ctx.print_color_centered(22, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Turns taken: {}", turns));
+    ctx.print_color_centered(23, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), &format!("Seed: {}", seed));
+    ctx.print_color_centered(27, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to the menu");
+
+    ctx.key.is_some()
+}
+
+//Function to draw the game over screen, returning true once the player is done with it
+pub fn game_over(ecs: &World, ctx : &mut Rltk) -> bool {
+    run_summary(ecs, ctx, "You have died!", RGB::named(rltk::RED))
+}
+
+//Function to draw the victory screen, returning true once the player is done with it
+pub fn victory(ecs: &World, ctx : &mut Rltk) -> bool {
+    run_summary(ecs, ctx, "You have escaped the dungeon!", RGB::named(rltk::GOLD))
+}
@@ -0,0 +1,63 @@
+use specs::prelude::*;
+use super::{HungerClock, HungerState, ProvidesFood, Health, Player, GameLog, RunState};
+
+//How many turns the player spends in each hunger state before getting hungrier
+pub const WELL_FED_TURNS : i32 = 300;
+const NORMAL_TURNS : i32 = 300;
+const HUNGRY_TURNS : i32 = 300;
+
+//How much health starving takes away each turn
+const STARVING_DAMAGE : i32 = 1;
+
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = (Entities<'a>, WriteStorage<'a, HungerClock>, WriteStorage<'a, Health>, ReadStorage<'a, Player>, WriteExpect<'a, GameLog>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        //Get the necessary data from the ECS
+        let (entities, mut clocks, mut healths, player, mut log) = data;
+
+        //Count down every hunger clock, moving on to the next state when one runs out
+        for (entity, clock) in (&entities, &mut clocks).join() {
+            let is_player = player.get(entity).is_some();
+            clock.duration -= 1;
+            if clock.duration < 1 {
+                let (state, duration, message) = match clock.state {
+                    HungerState::WellFed => (HungerState::Normal, NORMAL_TURNS, "You are no longer well fed."),
+                    HungerState::Normal => (HungerState::Hungry, HUNGRY_TURNS, "You are hungry."),
+                    HungerState::Hungry | HungerState::Starving => (HungerState::Starving, 0, "You are starving!")
+                };
+                if is_player && clock.state != HungerState::Starving {
+                    log.entries.push(message.to_string());
+                }
+                clock.state = state;
+                clock.duration = duration;
+            }
+
+            //Starving wears away at health every turn until something is eaten
+            if clock.state == HungerState::Starving {
+                if let Some(health) = healths.get_mut(entity) {
+                    health.hp -= STARVING_DAMAGE;
+                    if is_player && health.hp <= 0 {
+                        log.entries.push("You starve to death.".to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+//Function to eat a food item, which is used up and leaves the player well fed, returning the run state that follows
+pub fn eat_food(ecs : &mut World, food_entity : Entity) -> RunState {
+    if ecs.read_storage::<ProvidesFood>().get(food_entity).is_none() { return RunState::Paused; }
+    ecs.delete_entity(food_entity).expect("Unable to delete entity");
+
+    let player_entity = *ecs.fetch::<Entity>();
+    if let Some(clock) = ecs.write_storage::<HungerClock>().get_mut(player_entity) {
+        clock.state = HungerState::WellFed;
+        clock.duration = WELL_FED_TURNS;
+    }
+    ecs.write_resource::<GameLog>().entries.push("You eat a ration and feel well fed.".to_string());
+    RunState::Running
+}
@@ -0,0 +1,176 @@
+use rltk::VirtualKeyCode;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+//Path of the data file the key bindings are loaded from
+pub const KEYMAP_PATH : &str = "resources/keymap.json";
+
+//Actions that keys can be bound to
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    MoveLeft, MoveRight, MoveUp, MoveDown,
+    MoveUpLeft, MoveUpRight, MoveDownLeft, MoveDownRight,
+    Wait, Interact, Descend,
+    AutoExplore, TravelToStairs,
+    ToggleOverview, Look, ToggleCameraClamp, ToggleBoundaries, ToggleRenderMode
+}
+
+//Lookup table from keys to the actions bound to them
+pub struct Keymap {
+    bindings : HashMap<VirtualKeyCode, KeyAction>
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let mut keymap = Keymap{ bindings : HashMap::new() };
+        keymap.bind(KeyAction::MoveLeft, &[VirtualKeyCode::Left, VirtualKeyCode::A, VirtualKeyCode::Numpad4, VirtualKeyCode::H]);
+        keymap.bind(KeyAction::MoveRight, &[VirtualKeyCode::Right, VirtualKeyCode::D, VirtualKeyCode::Numpad6, VirtualKeyCode::L]);
+        keymap.bind(KeyAction::MoveUp, &[VirtualKeyCode::Up, VirtualKeyCode::W, VirtualKeyCode::Numpad8, VirtualKeyCode::K]);
+        keymap.bind(KeyAction::MoveDown, &[VirtualKeyCode::Down, VirtualKeyCode::S, VirtualKeyCode::Numpad2, VirtualKeyCode::J]);
+        keymap.bind(KeyAction::MoveUpLeft, &[VirtualKeyCode::Numpad7, VirtualKeyCode::Y]);
+        keymap.bind(KeyAction::MoveUpRight, &[VirtualKeyCode::Numpad9, VirtualKeyCode::U]);
+        keymap.bind(KeyAction::MoveDownLeft, &[VirtualKeyCode::Numpad1, VirtualKeyCode::B]);
+        keymap.bind(KeyAction::MoveDownRight, &[VirtualKeyCode::Numpad3, VirtualKeyCode::N]);
+        keymap.bind(KeyAction::Wait, &[VirtualKeyCode::Numpad5, VirtualKeyCode::Period, VirtualKeyCode::Space]);
+        keymap.bind(KeyAction::Interact, &[VirtualKeyCode::E]);
+        keymap.bind(KeyAction::Descend, &[VirtualKeyCode::Return]);
+        keymap.bind(KeyAction::AutoExplore, &[VirtualKeyCode::O]);
+        keymap.bind(KeyAction::TravelToStairs, &[VirtualKeyCode::T]);
+        keymap.bind(KeyAction::ToggleOverview, &[VirtualKeyCode::M]);
+        keymap.bind(KeyAction::Look, &[VirtualKeyCode::X]);
+        keymap.bind(KeyAction::ToggleCameraClamp, &[VirtualKeyCode::F1]);
+        keymap.bind(KeyAction::ToggleBoundaries, &[VirtualKeyCode::F2]);
+        keymap.bind(KeyAction::ToggleRenderMode, &[VirtualKeyCode::F3]);
+        keymap
+    }
+}
+
+impl KeyAction {
+    //Function to get the direction of a movement action, if this is one
+    pub fn direction(self) -> Option<(i32, i32)> {
+        match self {
+            KeyAction::MoveLeft => Some((-1, 0)),
+            KeyAction::MoveRight => Some((1, 0)),
+            KeyAction::MoveUp => Some((0, -1)),
+            KeyAction::MoveDown => Some((0, 1)),
+            KeyAction::MoveUpLeft => Some((-1, -1)),
+            KeyAction::MoveUpRight => Some((1, -1)),
+            KeyAction::MoveDownLeft => Some((-1, 1)),
+            KeyAction::MoveDownRight => Some((1, 1)),
+            _ => None
+        }
+    }
+}
+
+impl Keymap {
+    //Function to load the key bindings from a data file, using the defaults if there is no file and stopping with a clear message if it can't be read or parsed
+    pub fn load(path : &str) -> Keymap {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Keymap::default(),
+            Err(err) => panic!("Unable to read keymap {}: {}", path, err)
+        };
+        Keymap::parse(&data).unwrap_or_else(|problem| panic!("Invalid keymap {}: {}", path, problem))
+    }
+
+    //Function to build the key bindings from the contents of a keymap file, which rebinds the actions it lists and leaves the rest on their default keys
+    pub fn parse(data : &str) -> Result<Keymap, String> {
+        //The file maps each action to a list of key names
+        let file_bindings : HashMap<KeyAction, Vec<String>> = serde_json::from_str(data).map_err(|err| err.to_string())?;
+
+        //Work out the keys the file binds, refusing a key bound to two different actions
+        let mut file_keys : HashMap<VirtualKeyCode, KeyAction> = HashMap::new();
+        for (action, key_names) in file_bindings.iter() {
+            for key_name in key_names.iter() {
+                let key = key_from_name(key_name).ok_or_else(|| format!("unknown key {}", key_name))?;
+                if let Some(other) = file_keys.insert(key, *action).filter(|other| other != action) {
+                    return Err(format!("{} is bound to both {:?} and {:?}", key_name, other, action));
+                }
+            }
+        }
+
+        //Replace the default keys of every action the file lists, then add the file's keys over the defaults
+        let mut keymap = Keymap::default();
+        keymap.bindings.retain(|_key, action| !file_bindings.contains_key(action));
+        keymap.bindings.extend(file_keys);
+        Ok(keymap)
+    }
+
+    //Function to bind a list of keys to an action
+    fn bind(&mut self, action : KeyAction, keys : &[VirtualKeyCode]) {
+        for key in keys.iter() {
+            self.bindings.insert(*key, action);
+        }
+    }
+
+    //Function to get the action bound to a key, if there is one
+    pub fn action(&self, key : VirtualKeyCode) -> Option<KeyAction> {
+        self.bindings.get(&key).copied()
+    }
+
+    //Function to get the name of a key bound to an action, to show the player which key to press
+    pub fn key_name(&self, action : KeyAction) -> Option<&'static str> {
+        KEY_NAMES.iter().find(|(_key_name, key)| self.action(*key) == Some(action)).map(|(key_name, _key)| *key_name)
+    }
+}
+
+//Names of the keys that can be bound, as written in the keymap file
+const KEY_NAMES : &[(&str, VirtualKeyCode)] = {
+    use VirtualKeyCode::*;
+    &[
+        ("A", A), ("B", B), ("C", C), ("D", D), ("E", E), ("F", F), ("G", G), ("H", H), ("I", I),
+        ("J", J), ("K", K), ("L", L), ("M", M), ("N", N), ("O", O), ("P", P), ("Q", Q), ("R", R),
+        ("S", S), ("T", T), ("U", U), ("V", V), ("W", W), ("X", X), ("Y", Y), ("Z", Z),
+        ("Key0", Key0), ("Key1", Key1), ("Key2", Key2), ("Key3", Key3), ("Key4", Key4),
+        ("Key5", Key5), ("Key6", Key6), ("Key7", Key7), ("Key8", Key8), ("Key9", Key9),
+        ("Numpad0", Numpad0), ("Numpad1", Numpad1), ("Numpad2", Numpad2), ("Numpad3", Numpad3), ("Numpad4", Numpad4),
+        ("Numpad5", Numpad5), ("Numpad6", Numpad6), ("Numpad7", Numpad7), ("Numpad8", Numpad8), ("Numpad9", Numpad9),
+        ("F1", F1), ("F2", F2), ("F3", F3), ("F4", F4), ("F5", F5), ("F6", F6),
+        ("F7", F7), ("F8", F8), ("F9", F9), ("F10", F10), ("F11", F11), ("F12", F12),
+        ("Left", Left), ("Right", Right), ("Up", Up), ("Down", Down),
+        ("Space", Space), ("Return", Return), ("Escape", Escape), ("Tab", Tab), ("Back", Back),
+        ("Period", Period), ("Comma", Comma), ("Slash", Slash), ("Semicolon", Semicolon),
+        ("Minus", Minus), ("Equals", Equals)
+    ]
+};
+
+//Function to get the key with the given name, as written in the keymap file
+fn key_from_name(name : &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES.iter().find(|(key_name, _key)| *key_name == name).map(|(_key_name, key)| *key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_rebinds_only_the_actions_it_lists() {
+        let keymap = Keymap::parse(r#"{ "wait": ["Z"], "interact": ["G", "Return"] }"#).expect("Keymap should parse");
+        assert_eq!(keymap.action(VirtualKeyCode::Z), Some(KeyAction::Wait));
+        assert_eq!(keymap.action(VirtualKeyCode::Space), None); //The default wait keys are replaced
+        assert_eq!(keymap.action(VirtualKeyCode::G), Some(KeyAction::Interact));
+        assert_eq!(keymap.action(VirtualKeyCode::E), None);
+        assert_eq!(keymap.action(VirtualKeyCode::Return), Some(KeyAction::Interact)); //Taken from descend, which keeps its other keys
+        assert_eq!(keymap.action(VirtualKeyCode::Left), Some(KeyAction::MoveLeft)); //Actions left out keep their default keys
+        assert_eq!(keymap.key_name(KeyAction::ToggleOverview), Some("M"));
+    }
+
+    #[test]
+    fn shipped_file_matches_the_defaults() {
+        let shipped = Keymap::parse(include_str!("../resources/keymap.json")).expect("Shipped keymap should parse");
+        assert_eq!(shipped.bindings, Keymap::default().bindings);
+    }
+
+    #[test]
+    fn key_bound_to_two_actions_is_rejected() {
+        let problem = Keymap::parse(r#"{ "wait": ["Z"], "look": ["Z"] }"#).err().expect("Duplicate key should be rejected");
+        assert!(problem.contains("Wait") && problem.contains("Look"), "{}", problem);
+    }
+
+    #[test]
+    fn unknown_key_or_action_is_rejected() {
+        assert!(Keymap::parse(r#"{ "wait": ["NoSuchKey"] }"#).err().expect("Unknown key should be rejected").contains("NoSuchKey"));
+        assert!(Keymap::parse(r#"{ "dance": ["Z"] }"#).is_err());
+    }
+}
@@ -0,0 +1,18 @@
+use rltk::Rltk;
+
+//Consoles the screen is drawn on, from the bottom up; a layer only covers the ones under it where something has been drawn on it
+pub const TILE_CONSOLE : usize = 0; //Sprites from the tileset
+pub const ASCII_CONSOLE : usize = 1; //Map and entity glyphs
+pub const EFFECTS_CONSOLE : usize = 2; //Particles and other short lived effects
+pub const HUD_CONSOLE : usize = 3; //Interface drawn over everything else
+
+const LAYERS : [usize; 4] = [TILE_CONSOLE, ASCII_CONSOLE, EFFECTS_CONSOLE, HUD_CONSOLE];
+
+//Function to clear every layer, leaving the ASCII console active
+pub fn clear_layers(ctx : &mut Rltk) {
+    for layer in LAYERS.iter() {
+        ctx.set_active_console(*layer);
+        ctx.cls();
+    }
+    ctx.set_active_console(ASCII_CONSOLE);
+}
@@ -0,0 +1,17 @@
+//The map, the components and the visibility code, shared by the game and the field of view benchmark
+mod components;
+pub use components::*;
+mod map;
+pub use map::*;
+mod map_config;
+pub use map_config::*;
+mod terrain;
+mod water;
+mod rect;
+pub use rect::Rect;
+mod bitset;
+pub use bitset::BitSet;
+mod fov;
+pub use fov::FovAlgorithm;
+mod visibility_system;
+pub use visibility_system::{VisibilitySystem, can_see, seen_by};
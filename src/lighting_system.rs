@@ -0,0 +1,36 @@
+use specs::prelude::*;
+use super::{Map, Position, LightSource};
+use rltk::{field_of_view, Point, RGB};
+
+//Light level of tiles that no light source reaches
+const AMBIENT_LIGHT : (f32, f32, f32) = (0.0, 0.0, 0.0);
+
+pub struct LightingSystem {}
+
+impl<'a> System<'a> for LightingSystem {
+    type SystemData = (WriteExpect<'a, Map>, ReadStorage<'a, Position>, ReadStorage<'a, LightSource>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        //Get the necessary data from the ECS
+        let (mut map, positions, lights) = data;
+
+        //Reset every tile to the ambient light level
+        let ambient = RGB::from_f32(AMBIENT_LIGHT.0, AMBIENT_LIGHT.1, AMBIENT_LIGHT.2);
+        for l in map.light.iter_mut() { *l = ambient };
+
+        for (pos, light) in (&positions, &lights).join() { //All entities with a light source and a position
+            //Light every tile the source can see, fading out towards the edge of its radius
+            let origin = Point::new(pos.x, pos.y);
+            let lit_tiles = field_of_view(origin, light.radius, &*map);
+            for tile in lit_tiles.iter() {
+                let idx = match map.try_idx(tile.x, tile.y) {
+                    Some(idx) => idx,
+                    None => continue
+                };
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(origin, *tile) / light.radius as f32;
+                let intensity = (1.0 - distance * distance).max(0.0);
+                map.light[idx] = map.light[idx] + light.color * intensity;
+            }
+        }
+    }
+}
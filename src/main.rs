@@ -1,34 +1,110 @@
 use rltk::{Rltk, GameState, RGB, Point, RandomNumberGenerator};
 use specs::prelude::*;
+use rogueliketutorial::*;
 
-mod components;
-pub use components::*;
-mod map;
-pub use map::*;
 mod player;
 use player::*;
+mod command;
+pub use command::*;
+mod keymap;
+pub use keymap::*;
+mod replay;
+pub use replay::*;
+mod travel;
+pub use travel::*;
+mod gui;
+use gui::{MainMenuSelection, MainMenuResult};
+mod gamelog;
+mod minimap;
+mod tileset;
+pub use tileset::*;
+mod layers;
+pub use layers::*;
+pub use gamelog::GameLog;
 pub mod camera;
-mod rect;
-pub use rect::Rect;
-mod visibility_system;
-use visibility_system::VisibilitySystem;
+pub use camera::CameraSettings;
+mod lighting_system;
+use lighting_system::LightingSystem;
+mod memory_system;
+use memory_system::MemorySystem;
+mod spawner;
+mod status_system;
+use status_system::StatusSystem;
+mod shrine;
+pub use shrine::use_shrine;
+mod hunger_system;
+use hunger_system::HungerSystem;
+pub use hunger_system::{eat_food, WELL_FED_TURNS};
+mod particle_system;
+pub use particle_system::ParticleBuilder;
+use particle_system::ParticleSpawnSystem;
 
 //Create game state
 #[derive(PartialEq, Copy, Clone)]
-pub enum RunState {Paused, Running, NextLevel}
+pub enum RunState {
+    MainMenu { selection : MainMenuSelection, can_continue : bool },
+    Paused, Running, NextLevel, Overview, Look,
+    MagicMapReveal { row : i32 },
+    GameOver, Victory
+}
 
 pub struct State {
     pub ecs: World,
     pub runstate : RunState
 }
 
+//How many rows of the map magic mapping reveals each frame
+const MAGIC_MAP_ROWS_PER_FRAME : i32 = 2;
+
+//Resource counting the turns that have passed in the current run
+#[derive(Default)]
+pub struct TurnCounter {
+    pub turn : u64
+}
+
+//Function to get a seed from the clock, so every run is different but can still be reproduced from its recording
+fn clock_seed() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).expect("Clock is before the Unix epoch").as_nanos() as u64
+}
+
+//Function to get the main menu run state, offering to continue if there is an unfinished run saved
+fn main_menu_state() -> RunState {
+    let can_continue = Replay::can_continue(REPLAY_PATH);
+    let selection = if can_continue { MainMenuSelection::Continue } else { MainMenuSelection::NewGame };
+    RunState::MainMenu{ selection, can_continue }
+}
+
 impl State {
-    //Function to run systems
+    //Function to run systems for a turn the player has taken
     fn run_systems(&mut self) {
+        //Run the status system, so effects that wear off this turn are gone before visibility is worked out
+        let mut status = StatusSystem{};
+        status.run_now(&self.ecs);
+
+        //Run the hunger system, so the player only gets hungrier on turns they act
+        let mut hunger = HungerSystem{};
+        hunger.run_now(&self.ecs);
+
+        self.update_view();
+
+        //A turn has passed
+        self.ecs.write_resource::<TurnCounter>().turn += 1;
+    }
+
+    //Function to run the systems working out what the player can see, without any time passing
+    fn update_view(&mut self) {
+        //Run the lighting system, so visibility knows which tiles are lit
+        let mut lighting = LightingSystem{};
+        lighting.run_now(&self.ecs);
+
         //Run the visibility system
         let mut vis = VisibilitySystem{};
         vis.run_now(&self.ecs);
 
+        //Run the memory system, so static entities the player can see are remembered once they're out of sight
+        let mut memory = MemorySystem{};
+        memory.run_now(&self.ecs);
+
         self.ecs.maintain();
     }
 
@@ -67,19 +143,17 @@ impl State {
         let worldmap;
         {
             let mut worldmap_resource = self.ecs.write_resource::<Map>();
+            let map_config = self.ecs.fetch::<MapConfig>();
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
             let current_depth = worldmap_resource.depth;
-
-            //Create a new random number generator
-            let mut rng = RandomNumberGenerator::new();
-            if rng.range(0, 3) == 1 {
-                *worldmap_resource = Map::new_map_cellular_automata(current_depth + 1, 100, 100);
-            }else {
-                *worldmap_resource = Map::new_map_rooms_and_corridors(current_depth + 1, 100, 100);
-            }
+            *worldmap_resource = Map::new_map_for_depth(current_depth + 1, &*map_config, &mut *rng);
 
             worldmap = worldmap_resource.clone();
         }
 
+        //Fill the new map with its features
+        spawner::populate_level(&mut self.ecs);
+
         //Place the player in the first room of the new map
         let (player_x, player_y) = (worldmap.starting_position_x, worldmap.starting_position_y); //Set the player's start position in the new map
         let mut player_position = self.ecs.write_resource::<Point>(); //Get the Point tracking the player's position
@@ -92,6 +166,9 @@ impl State {
             player_pos_comp.y = player_y;
         }
 
+        //Fade the new level in around the player
+        self.ecs.write_resource::<ParticleBuilder>().fade_in(player_x, player_y, 6);
+
         //Mark the player's viewshed as dirty so visibility is recalculated
         let mut viewshed_components = self.ecs.write_storage::<Viewshed>(); //Get write access to the ECS's Viewshed component storage
         let viewshed = viewshed_components.get_mut(*player_entity); //Get the Viewshed component associated with the player entity
@@ -99,81 +176,381 @@ impl State {
             vs.dirty = true;
         }        
     }
+
+    //Function to throw away the current run, if there is one, and start a new run from the seed of the given recorder
+    fn new_game(&mut self, recorder : ReplayRecorder) {
+        let seed = recorder.replay.seed;
+
+        //Delete every entity from the previous run
+        self.ecs.delete_all();
+        self.ecs.maintain();
+
+        //Reset the resources that belong to a single run
+        self.ecs.insert(ReplayPlayback::default());
+        self.ecs.insert(recorder);
+        self.ecs.insert(TurnCounter::default());
+        self.ecs.insert(Travel::default());
+        self.ecs.insert(GameLog::default());
+        self.ecs.insert(ParticleBuilder::default());
+        self.ecs.write_resource::<CameraSettings>().look_cursor = None;
+
+        //Create the run's seeded random number generator
+        let mut rng = RandomNumberGenerator::seeded(seed);
+
+        //Add a new randomly generated map to the ECS as a resource
+        let map = {
+            let map_config = self.ecs.fetch::<MapConfig>();
+            let (map_width, map_height) = map_config.dimensions_for_depth(1);
+            let mut map = Map::new_map_cellular_automata(1, map_width, map_height, &map_config.cellular_automata.for_depth(1), &mut rng);
+            map.apply_post_processing(&*map_config, &mut rng);
+            map
+        };
+        let (player_x, player_y) = (map.starting_position_x, map.starting_position_y); //Set the player's start position in the new map
+        self.ecs.insert(map);
+        self.ecs.insert(rng);
+
+        //Create a player entity with Position and Renderable components and a Player tag component
+        let player_entity = self.ecs
+            .create_entity()
+            .with(Position { x: player_x, y: player_y })
+            .with(Renderable {
+                glyph: rltk::to_cp437('@'),
+                fg: RGB::named(rltk::WHITE),
+                bg: RGB::named(rltk::BLACK),
+                render_order: 0
+            })
+            .with(Player{})
+            .with(Name{ name : "Player".to_string() })
+            .with(Viewshed::new(8))
+            .with(LightSource{ radius : 8, color : RGB::from_f32(1.0, 0.85, 0.6) }) //The player's torch
+            .with(Health{ hp : 30, max_hp : 30 })
+            .with(HungerClock{ state : HungerState::WellFed, duration : WELL_FED_TURNS })
+            .build();
+        self.ecs.insert(player_entity);
+
+        //Fill the first map with its features
+        spawner::populate_level(&mut self.ecs);
+
+        //Keep track of the player's position with a Point
+        self.ecs.insert(Point::new(player_x, player_y));
+
+        //Work out what the player can see to begin with, then wait for their first command, so no time passes before they act
+        self.update_view();
+        self.runstate = RunState::Paused;
+    }
+
+    //Function to continue the saved run by replaying its recording from the start, without drawing anything along the way
+    fn continue_game(&mut self) {
+        //Nothing is saved while catching up, so the file is left alone if the replay doesn't get to the end
+        let replay = Replay::load(REPLAY_PATH);
+        let commands = replay.commands.iter().copied().collect();
+        self.new_game(ReplayRecorder::new(replay.seed, None));
+        self.ecs.write_resource::<ReplayPlayback>().commands = commands;
+        loop {
+            match self.runstate {
+                RunState::Running | RunState::NextLevel => self.take_turn(),
+                RunState::MagicMapReveal{ row } => self.runstate = self.reveal_map_rows(row),
+                RunState::Paused => {
+                    match next_replay_command(&mut self.ecs) {
+                        Some(command) => self.runstate = self.perform_command(command),
+                        None => break
+                    }
+                }
+                _ => break
+            }
+        }
+
+        //Save the commands that were actually replayed in place of the old recording, dropping any that no longer matched the run, so new commands follow on from the state that was reached
+        self.ecs.write_resource::<ReplayRecorder>().save_to(REPLAY_PATH);
+    }
+
+    //Function to reveal the next few rows of the level's layout for magic mapping, returning the run state that follows; the turn is taken once the sweep reaches the bottom
+    fn reveal_map_rows(&mut self, row : i32) -> RunState {
+        let mut map = self.ecs.write_resource::<Map>();
+        let last_row = i32::min(row + MAGIC_MAP_ROWS_PER_FRAME, map.height);
+        for y in row .. last_row {
+            map.reveal_layout_row(y);
+        }
+        if last_row >= map.height { RunState::Running } else { RunState::MagicMapReveal{ row : last_row } }
+    }
+
+    //Function to check if the player has run out of health
+    fn player_is_dead(&self) -> bool {
+        let player_entity = self.ecs.fetch::<Entity>();
+        let healths = self.ecs.read_storage::<Health>();
+        match healths.get(*player_entity) {
+            Some(health) => health.hp <= 0,
+            None => false
+        }
+    }
+
+    //Function to end the run, marking its recording as finished so it can't be continued
+    fn end_run(&mut self, runstate : RunState) {
+        self.ecs.write_resource::<Travel>().stop();
+        self.ecs.write_resource::<ReplayRecorder>().finish();
+        self.runstate = runstate;
+    }
+
+    //Function to take a turn after a command, going down to the next level first if the command asked to
+    fn take_turn(&mut self) {
+        if self.runstate == RunState::NextLevel {
+            //Taking the stairs on the final level leads out of the dungeon
+            let final_depth = self.ecs.fetch::<MapConfig>().final_depth;
+            if self.ecs.fetch::<Map>().depth >= final_depth {
+                self.ecs.write_resource::<GameLog>().entries.push("You climb out in to daylight.".to_string());
+                self.end_run(RunState::Victory);
+                return;
+            }
+            self.goto_next_level();
+        }
+
+        self.run_systems();
+
+        if self.player_is_dead() {
+            self.end_run(RunState::GameOver);
+        } else {
+            self.runstate = RunState::Paused;
+        }
+    }
+
+    //Function to record a command in the replay and perform it
+    fn perform_command(&mut self, command : Command) -> RunState {
+        let turn = self.ecs.fetch::<TurnCounter>().turn;
+        self.ecs.write_resource::<ReplayRecorder>().record(turn, command);
+        execute_command(&mut self.ecs, command)
+    }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx : &mut Rltk) {
-        //Clear the screen
-        ctx.cls();
+        //Clear the screen, leaving the ASCII console active for everything that isn't a sprite
+        layers::clear_layers(ctx);
+
+        //The menu and end of run screens replace the map entirely
+        ctx.set_active_console(HUD_CONSOLE);
+        match self.runstate {
+            RunState::MainMenu{ selection, can_continue } => {
+                match gui::main_menu(ctx, selection, can_continue) {
+                    MainMenuResult::NoSelection{ selected } => self.runstate = RunState::MainMenu{ selection : selected, can_continue },
+                    MainMenuResult::Selected{ selected } => {
+                        match selected {
+                            MainMenuSelection::NewGame => self.new_game(ReplayRecorder::new(clock_seed(), Some(REPLAY_PATH))),
+                            MainMenuSelection::Continue => self.continue_game(),
+                            MainMenuSelection::Quit => ctx.quitting = true
+                        }
+                    }
+                }
+                return;
+            }
+            RunState::GameOver => {
+                if gui::game_over(&self.ecs, ctx) {
+                    self.runstate = main_menu_state();
+                }
+                return;
+            }
+            RunState::Victory => {
+                if gui::victory(&self.ecs, ctx) {
+                    self.runstate = main_menu_state();
+                }
+                return;
+            }
+            _ => {}
+        }
+        ctx.set_active_console(ASCII_CONSOLE);
+
+        //Age and create particles every frame, whether or not a turn is being taken
+        particle_system::cull_dead_particles(&mut self.ecs, ctx);
+        let mut particles = ParticleSpawnSystem{};
+        particles.run_now(&self.ecs);
 
         //Run the game if it isn't paused, otherwise wait for input
-        if self.runstate == RunState::Running {
-            //Run systems
-            self.run_systems();
-            self.runstate = RunState::Paused;
-        }else if self.runstate == RunState::Paused {
-            //Read user input from RLTK
-            self.runstate = user_input(self, ctx);
-        }else if self.runstate == RunState::NextLevel {
-            self.goto_next_level();
-            self.run_systems();
-            self.runstate = RunState::Paused;
-        }        
+        match self.runstate {
+            RunState::Running | RunState::NextLevel => {
+                //Run systems, going down a level first if needed
+                self.take_turn();
+            }
+            RunState::Paused => {
+                //Feed in the next command of a replay if one is being played back, otherwise read user input from RLTK, and perform the resulting command
+                let command = match next_replay_command(&mut self.ecs) {
+                    Some(command) => Some(command),
+                    None => user_input(self, ctx)
+                };
+                if let Some(command) = command {
+                    self.runstate = self.perform_command(command);
+                }
+            }
+            RunState::Overview => {
+                //Wait for the player to close the overview
+                self.runstate = overview_input(self, ctx);
+            }
+            RunState::Look => {
+                //Move the look cursor around until the player is done
+                self.runstate = look_input(self, ctx);
+            }
+            RunState::MagicMapReveal{ row } => {
+                //Sweep down the map a few rows a frame
+                self.runstate = self.reveal_map_rows(row);
+            }
+            _ => {}
+        }
+
+        //The turn may have ended the run, in which case its screen is drawn from the next frame
+        if self.runstate == RunState::GameOver || self.runstate == RunState::Victory { return; }
 
-        //Draw the map
-        camera::render_camera(&self.ecs, ctx);
+        if self.runstate == RunState::Overview {
+            //Draw the whole map shrunk to fit the screen, with its title on the HUD layer
+            minimap::draw_overview(&self.ecs, ctx);
+            ctx.set_active_console(HUD_CONSOLE);
+        } else {
+            //Draw the map
+            camera::render_camera(&self.ecs, ctx);
 
-        //Draw a simple HUD with the current map depth
-        let map = self.ecs.fetch::<Map>();
-        let depth = format!("Depth: {}", map.depth);
-        ctx.print_color(1, 48, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &depth);
+            //Draw the minimap in the corner, on the HUD layer with the rest of the interface
+            ctx.set_active_console(HUD_CONSOLE);
+            minimap::draw_minimap(&self.ecs, ctx);
+        }
+
+        //Draw a simple HUD with the current map depth and latest message
+        gui::draw_ui(&self.ecs, ctx);
+
+        //Describe whatever is under the look cursor, or the mouse
+        if self.runstate == RunState::Look {
+            let cursor = self.ecs.fetch::<CameraSettings>().look_cursor;
+            if let Some(cursor) = cursor {
+                let (min_x, _max_x, min_y, _max_y) = camera::get_screen_bounds(&self.ecs, ctx);
+                gui::draw_tooltip_at(&self.ecs, ctx, (cursor.x - min_x, cursor.y - min_y));
+            }
+        } else if self.runstate != RunState::Overview {
+            gui::draw_tooltip(&self.ecs, ctx);
+        }
     }
 }
 
+//Function to tell the ECS (World) about every component, so it can store them
+fn register_components(ecs : &mut World) {
+    ecs.register::<Position>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Name>();
+    ecs.register::<LightSource>();
+    ecs.register::<ParticleLifetime>();
+    ecs.register::<Health>();
+    ecs.register::<TileSize>();
+    ecs.register::<Memorable>();
+    ecs.register::<Monster>();
+    ecs.register::<Hidden>();
+    ecs.register::<Shrine>();
+    ecs.register::<Telepathy>();
+    ecs.register::<HungerClock>();
+    ecs.register::<ProvidesFood>();
+}
+
 //Main method
 fn main() -> rltk::BError {
     //Set up the RLTK 80x50 windows
     use rltk::RltkBuilder;
-    let mut context = RltkBuilder::simple80x50()
+    let mut context = RltkBuilder::new()
         .with_title("Roguelike Tutorial")
+        .with_dimensions(80, 50)
         .with_tile_dimensions(16, 16)
+        .with_font("terminal8x8.png", 8, 8)
+        .with_font(TILESET_FONT, 16, 16)
+        .with_simple_console(80, 50, TILESET_FONT) //The tileset console, drawn first so it sits under the ASCII console
+        .with_sparse_console(80, 50, "terminal8x8.png") //The ASCII console
+        .with_sparse_console(80, 50, "terminal8x8.png") //The effects console
+        .with_sparse_console(80, 50, "terminal8x8.png") //The HUD console
         .build()?;
     context.with_post_scanlines(true);
     context.screen_burn_color(RGB::from_u8(0, 0, 0));
 
-    //Set the game state with a new ECS (World)
+    //Set the game state with a new ECS (World), starting on the main menu
     let mut gs = State { 
         ecs: World::new(),
-        runstate : RunState::Running
+        runstate : main_menu_state()
     };
     //Tell the ECS (World) about the components so it can store them
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-
-    //Add a new randomly generated map to the ECS as a resource
-    let map = Map::new_map_cellular_automata(1, 100, 100);
-    let (player_x, player_y) = (map.starting_position_x, map.starting_position_y); //Set the player's start position in the new map
-    gs.ecs.insert(map);
-
-    //Create a player entity with Position and Renderable components and a Player tag component
-    let player_entity = gs.ecs
-        .create_entity()
-        .with(Position { x: player_x, y: player_y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('@'),
-            fg: RGB::named(rltk::WHITE),
-            bg: RGB::named(rltk::BLACK),
-        })
-        .with(Player{})
-        .with(Viewshed{ visible_tiles : Vec::new(), range : 8, dirty: true })
-        .build();
-    gs.ecs.insert(player_entity);
-
-    //Keep track of the player's position with a Point
-    gs.ecs.insert(Point::new(player_x, player_y));
+    register_components(&mut gs.ecs);
+
+    //Load the key bindings and add them to the ECS as a resource
+    gs.ecs.insert(Keymap::load(KEYMAP_PATH));
+
+    //Add the resources that last across runs
+    gs.ecs.insert(CameraSettings::default());
+    gs.ecs.insert(ParticleBuilder::default());
+    gs.ecs.insert(RenderMode::Ascii);
+    gs.ecs.insert(Tileset::load(TILESET_PATH));
+
+    //Load the map generation parameters and add them to the ECS as a resource
+    gs.ecs.insert(MapConfig::load(MAP_CONFIG_PATH));
+
+    //Read the command line: "--replay <file>" plays back a recorded run, "--seed <number>" starts a run with a specific seed, and otherwise the main menu is shown
+    let args : Vec<String> = std::env::args().collect();
+    let arg_value = |name : &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+    if let Some(path) = arg_value("--replay") {
+        let replay = Replay::load(path);
+        gs.new_game(ReplayRecorder::new(replay.seed, None)); //Playing a replay back doesn't save over the run that can be continued
+        gs.ecs.write_resource::<ReplayPlayback>().commands = replay.commands.into_iter().collect();
+    } else if let Some(seed) = arg_value("--seed") {
+        gs.new_game(ReplayRecorder::new(seed.parse().expect("Seed must be a number"), Some(REPLAY_PATH)));
+    }
 
     //Start the RLTK main loop
     rltk::main_loop(context, gs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Function to create a game state with everything a run needs, without opening a window
+    fn test_state() -> State {
+        let mut gs = State{ ecs : World::new(), runstate : RunState::Paused };
+        register_components(&mut gs.ecs);
+        gs.ecs.insert(CameraSettings::default());
+        gs.ecs.insert(ParticleBuilder::default());
+        gs.ecs.insert(RenderMode::Ascii);
+        gs.ecs.insert(Tileset::default());
+        gs.ecs.insert(MapConfig::default());
+        gs.new_game(ReplayRecorder::new(7, None));
+        gs
+    }
+
+    #[test]
+    fn particles_dont_interrupt_explore_after_descending() {
+        let mut gs = test_state();
+        gs.runstate = RunState::NextLevel;
+        gs.take_turn();
+
+        //Clear out everything on the new level that should stop auto-explore, so only particles could
+        let to_delete : Vec<Entity> = {
+            let entities = gs.ecs.entities();
+            let monsters = gs.ecs.read_storage::<Monster>();
+            let food = gs.ecs.read_storage::<ProvidesFood>();
+            let shrines = gs.ecs.read_storage::<Shrine>();
+            (&entities, monsters.maybe(), food.maybe(), shrines.maybe()).join()
+                .filter(|(_entity, monster, food, shrine)| monster.is_some() || food.is_some() || shrine.is_some())
+                .map(|(entity, _monster, _food, _shrine)| entity)
+                .collect()
+        };
+        gs.ecs.delete_entities(&to_delete).expect("Unable to delete entities");
+        gs.ecs.maintain();
+
+        //The descent fade is spawned on the frame after the level is entered, as the player starts exploring
+        ParticleSpawnSystem{}.run_now(&gs.ecs);
+        start_explore(&mut gs.ecs);
+
+        for _ in 0..3 {
+            let command = next_travel_command(&mut gs.ecs).expect("Auto-explore was interrupted");
+            gs.runstate = gs.perform_command(command);
+            let player_pos = *gs.ecs.fetch::<Point>();
+            gs.ecs.write_resource::<ParticleBuilder>().flash(player_pos.x, player_pos.y, RGB::named(rltk::RED));
+            ParticleSpawnSystem{}.run_now(&gs.ecs);
+            gs.take_turn();
+
+            let particles = gs.ecs.read_storage::<ParticleLifetime>();
+            assert!(visible_entities(&gs.ecs).iter().all(|entity| particles.get(*entity).is_none()), "Particles shouldn't be seen as entities");
+        }
+        assert!(gs.ecs.fetch::<Travel>().is_active());
+    }
+}
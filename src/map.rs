@@ -1,10 +1,57 @@
-  use rltk::{RandomNumberGenerator, BaseMap, Algorithm2D, Point };
-use super::{Rect};
+  use rltk::{RandomNumberGenerator, BaseMap, Algorithm2D, Point, RGB };
+use super::{Rect, BitSet, MapConfig, RoomsAndCorridorsConfig, CellularAutomataConfig};
 use std::cmp::{max, min};
+use serde::{Serialize, Deserialize};
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
-    Wall, Floor, DownStairs
+    Wall, Floor, DownStairs, ShallowWater, DeepWater, Lava, Bridge, Rubble
+}
+
+impl TileType {
+    //Function to check if this type of tile can be walked on
+    pub fn is_walkable(self) -> bool {
+        match self {
+            TileType::Floor | TileType::DownStairs | TileType::ShallowWater | TileType::Bridge | TileType::Rubble => true,
+            TileType::Wall | TileType::DeepWater | TileType::Lava => false
+        }
+    }
+
+    //Function to check if this type of tile blocks line of sight
+    pub fn is_opaque(self) -> bool {
+        self == TileType::Wall
+    }
+
+    //Function to get the name of this type of tile, to describe it to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            TileType::Wall => "Wall",
+            TileType::Floor => "Floor",
+            TileType::DownStairs => "Down stairs",
+            TileType::ShallowWater => "Shallow water",
+            TileType::DeepWater => "Deep water",
+            TileType::Lava => "Lava",
+            TileType::Bridge => "Bridge",
+            TileType::Rubble => "Rubble"
+        }
+    }
+
+    //Function to get the cost of moving on to this type of tile, used to weight pathfinding
+    pub fn movement_cost(self) -> f32 {
+        match self {
+            TileType::ShallowWater => 2.0,
+            TileType::Rubble => 1.5,
+            _ => 1.0
+        }
+    }
+}
+
+//The glyph of a static entity as the player last saw it, drawn in its place once it is out of sight
+#[derive(Copy, Clone)]
+pub struct RememberedEntity {
+    pub glyph : rltk::FontCharType,
+    pub fg : RGB,
+    pub render_order : i32
 }
 
 #[derive(Default, Clone)]
@@ -17,7 +64,10 @@ pub struct Map {
     pub starting_position_x : i32,
     pub starting_position_y : i32,
     pub revealed_tiles : Vec<bool>,
-    pub visible_tiles : Vec<bool>
+    pub visible_tiles : BitSet,
+    pub wall_masks : Vec<u8>, //Cached bitmask of the revealed walls around each wall, updated as tiles are revealed
+    pub light : Vec<RGB>, //Colour and strength of the light falling on each tile
+    pub remembered_entities : Vec<Option<RememberedEntity>> //What the player last saw of the static entities on each tile
 }
 
 impl Algorithm2D for Map {
@@ -30,84 +80,225 @@ impl Algorithm2D for Map {
 impl BaseMap for Map {
     //Implements the BaseMap trait function to check if a tile at the given index can be seen through
     fn is_opaque(&self, idx:usize) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        self.tiles[idx as usize].is_opaque()
     }
 
     //Implements the BaseMap trait function to determine the distance between two points in pathfinding
     fn get_pathing_distance(&self, idx1:usize, idx2:usize) -> f32 {
-        let w = self.width as usize;
-        let p1 = Point::new(idx1 % w, idx1 / w);
-        let p2 = Point::new(idx2 % w, idx2 / w);
-        rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
+        let (x1, y1) = self.idx_xy(idx1);
+        let (x2, y2) = self.idx_xy(idx2);
+        rltk::DistanceAlg::Pythagoras.distance2d(Point::new(x1, y1), Point::new(x2, y2))
     }
 
     //Implements the BaseMap trait function to return a vector of tile indexes which can be entered from the tile at the given index 
     fn get_available_exits(&self, idx:usize) -> rltk::SmallVec<[(usize, f32); 10]> {
         let mut exits = rltk::SmallVec::new(); //Create a vector to store the valid exits
-        let x = idx as i32 % self.width;
-        let y = idx as i32 / self.width;
-        let w = self.width as usize;
+        let (x, y) = self.idx_xy(idx);
 
-        //Add valid exits in the cardinal directions
-        if self.is_exit_valid(x - 1, y) { exits.push((idx - 1, 1.0)) };
-        if self.is_exit_valid(x + 1, y) { exits.push((idx + 1, 1.0)) };
-        if self.is_exit_valid(x, y - 1) { exits.push((idx-w, 1.0)) };
-        if self.is_exit_valid(x, y + 1) { exits.push((idx+w, 1.0)) };
+        //Add valid exits in the cardinal directions, weighted by the cost of the tile being entered
+        for (nx, ny) in self.cardinal_neighbors(x, y) {
+            if let Some(exit_idx) = self.exit_idx(nx, ny) { exits.push((exit_idx, self.tiles[exit_idx].movement_cost())); }
+        }
 
         //Add valid exits in the diagonal directions
-        if self.is_exit_valid(x - 1, y - 1) { exits.push(((idx-w) - 1, 1.45)); }
-        if self.is_exit_valid(x + 1, y - 1) { exits.push(((idx-w) + 1, 1.45)); }
-        if self.is_exit_valid(x - 1, y + 1) { exits.push(((idx+w) - 1, 1.45)); }
-        if self.is_exit_valid(x + 1, y + 1) { exits.push(((idx+w) + 1, 1.45)); }
+        for (nx, ny) in self.diagonal_neighbors(x, y) {
+            if let Some(exit_idx) = self.exit_idx(nx, ny) { exits.push((exit_idx, 1.45 * self.tiles[exit_idx].movement_cost())); }
+        }
 
         //Return the list of valid exits
         exits
     }
 }
 
-impl Map { 
-    //Function to get a unique index for each map tile from its position
-    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
-        (y as usize * self.width as usize) + x as usize
-    }
+//Offsets to the tiles next to a tile, first in the cardinal directions and then the diagonal ones
+const CARDINAL_OFFSETS : [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL_OFFSETS : [(i32, i32); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
 
-    //Function to calculate if the given position can be entered
-    fn is_exit_valid(&self, x:i32, y:i32) -> bool {
-        if x < 1 || x > self.width-1 || y < 1 || y > self.height-1 { return false; }
-        let idx = self.xy_idx(x, y);
-        !(self.tiles[idx] == TileType::Wall)
-    }
+//Most times the cellular automata are run to get a usable cave before map generation gives up
+const MAX_CAVE_ATTEMPTS : i32 = 100;
 
-    //Function to create a new map with square rooms and corridors
-    pub fn new_map_rooms_and_corridors(new_depth : i32, width: i32, height: i32) -> Map {
+impl Map { 
+    //Cunstructor function to create a map of the given size that is all wall, for the generators to carve out
+    pub fn new(depth : i32, width : i32, height : i32) -> Map {
         //Calculate the total number of tiles in the map based on its width and height
         let map_tile_count = (width * height) as usize;
 
-        //Create the map struct
-        let mut map = Map {
+        Map {
             tiles : vec![TileType::Wall; map_tile_count],
             rooms : Vec::new(),
             width,
             height,
-            depth: new_depth,
+            depth,
             starting_position_x: 0,
             starting_position_y: 0,
             revealed_tiles : vec![false; map_tile_count],
-            visible_tiles : vec![false; map_tile_count]    
+            visible_tiles : BitSet::new(map_tile_count),
+            wall_masks : vec![0; map_tile_count],
+            light : vec![RGB::from_f32(0.0, 0.0, 0.0); map_tile_count],
+            remembered_entities : vec![None; map_tile_count]
+        }
+    }
+
+    //Function to check if a position is on the map
+    pub fn in_bounds(&self, x : i32, y : i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    //Function to get a unique index for each map tile from its position; the position must be on the map, so everything outside the map's own code goes through try_idx
+    fn xy_idx(&self, x: i32, y: i32) -> usize {
+        debug_assert!(self.in_bounds(x, y), "Position ({}, {}) is off the map", x, y);
+        (y as usize * self.width as usize) + x as usize
+    }
+
+    //Function to get the index of a position, or None if it is off the map
+    pub fn try_idx(&self, x : i32, y : i32) -> Option<usize> {
+        if self.in_bounds(x, y) { Some(self.xy_idx(x, y)) } else { None }
+    }
+
+    //Function to get the index of the player's starting position
+    pub fn start_idx(&self) -> usize {
+        self.xy_idx(self.starting_position_x, self.starting_position_y)
+    }
+
+    //Function to get the position of a tile from its index
+    pub fn idx_xy(&self, idx : usize) -> (i32, i32) {
+        (idx as i32 % self.width, idx as i32 / self.width)
+    }
+
+    //Function to iterate over the positions on the map next to a position in the cardinal directions
+    pub fn cardinal_neighbors(&self, x : i32, y : i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.offset_positions(x, y, &CARDINAL_OFFSETS)
+    }
+
+    //Function to iterate over the positions on the map diagonally next to a position
+    pub fn diagonal_neighbors(&self, x : i32, y : i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.offset_positions(x, y, &DIAGONAL_OFFSETS)
+    }
+
+    //Function to iterate over all eight positions on the map around a position
+    pub fn neighbors(&self, x : i32, y : i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.cardinal_neighbors(x, y).chain(self.diagonal_neighbors(x, y))
+    }
+
+    //Function to iterate over the positions on the map at the given offsets from a position
+    fn offset_positions(&self, x : i32, y : i32, offsets : &'static [(i32, i32)]) -> impl Iterator<Item = (i32, i32)> + '_ {
+        offsets.iter()
+            .map(move |(dx, dy)| (x + dx, y + dy))
+            .filter(move |(nx, ny)| self.in_bounds(*nx, *ny))
+    }
+
+    //Function to get the light falling on a tile, capped at full brightness
+    pub fn light_at(&self, idx : usize) -> RGB {
+        let light = self.light[idx];
+        RGB::from_f32(light.r.min(1.0), light.g.min(1.0), light.b.min(1.0))
+    }
+
+    //Function to check if a tile has enough light falling on it to be seen
+    pub fn is_lit(&self, idx : usize) -> bool {
+        let light = self.light[idx];
+        light.r + light.g + light.b > 0.15
+    }
+
+    //Function to mark a tile as revealed, updating the cached wall bitmasks it affects
+    pub fn reveal_tile(&mut self, idx : usize) {
+        if self.revealed_tiles[idx] { return; }
+        self.revealed_tiles[idx] = true;
+
+        //Only the tile itself and its neighbours can have a different bitmask now
+        let (x, y) = self.idx_xy(idx);
+        for (nx, ny) in [(x, y), (x, y - 1), (x, y + 1), (x - 1, y), (x + 1, y)].iter() {
+            if let Some(neighbor_idx) = self.try_idx(*nx, *ny) {
+                self.wall_masks[neighbor_idx] = self.compute_wall_mask(*nx, *ny);
+            }
+        }
+    }
+
+    //Function to reveal a row of the level's layout: every tile that isn't a wall, and the walls bordering them
+    pub fn reveal_layout_row(&mut self, y : i32) {
+        for x in 0..self.width {
+            let idx = self.xy_idx(x, y);
+            if self.tiles[idx] != TileType::Wall || self.borders_open_ground(x, y) {
+                self.reveal_tile(idx);
+            }
+        }
+    }
+
+    //Function to check if any of the tiles around a position aren't walls
+    fn borders_open_ground(&self, x : i32, y : i32) -> bool {
+        self.neighbors(x, y).any(|(nx, ny)| self.tiles[self.xy_idx(nx, ny)] != TileType::Wall)
+    }
+
+    //Function to forget every revealed tile, and what was remembered on it, that isn't currently visible
+    pub fn forget_unseen_tiles(&mut self) {
+        for idx in 0..self.revealed_tiles.len() {
+            if !self.visible_tiles[idx] {
+                self.revealed_tiles[idx] = false;
+                self.remembered_entities[idx] = None;
+            }
+        }
+
+        //Walls can now have fewer revealed walls around them
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.xy_idx(x, y);
+                self.wall_masks[idx] = self.compute_wall_mask(x, y);
+            }
+        }
+    }
+
+    //Function to get a bitmask of which neighbours of a tile are revealed walls: 1 north, 2 south, 4 west, 8 east
+    fn compute_wall_mask(&self, x : i32, y : i32) -> u8 {
+        let is_revealed_wall = |x : i32, y : i32| match self.try_idx(x, y) {
+            Some(idx) => self.revealed_tiles[idx] && self.tiles[idx] == TileType::Wall,
+            None => false
         };
-    
-        //Set the map's generation properties
-        const MAX_ROOMS : i32 = 100;
-        const MIN_SIZE : i32 = 10;
-        const MAX_SIZE : i32 = 18;
 
-        //Create a new random number generator
-        let mut rng = RandomNumberGenerator::new();
+        let mut mask = 0;
+        if is_revealed_wall(x, y - 1) { mask += 1; }
+        if is_revealed_wall(x, y + 1) { mask += 2; }
+        if is_revealed_wall(x - 1, y) { mask += 4; }
+        if is_revealed_wall(x + 1, y) { mask += 8; }
+        mask
+    }
+
+    //Function to get the index of the given position if it can be entered, never leading on to the top or left edge of the map
+    fn exit_idx(&self, x:i32, y:i32) -> Option<usize> {
+        if x < 1 || y < 1 { return None; }
+        self.try_idx(x, y).filter(|idx| self.tiles[*idx].is_walkable())
+    }
+
+    //Function to create a new map for the given depth with a randomly chosen algorithm
+    pub fn new_map_for_depth(new_depth : i32, config : &MapConfig, rng : &mut RandomNumberGenerator) -> Map {
+        let (width, height) = config.dimensions_for_depth(new_depth);
+        let mut map = if rng.roll_dice(1, 100) <= config.cellular_automata_chance {
+            Map::new_map_cellular_automata(new_depth, width, height, &config.cellular_automata.for_depth(new_depth), rng)
+        } else {
+            Map::new_map_rooms_and_corridors(new_depth, width, height, &config.rooms_and_corridors.for_depth(new_depth), rng)
+        };
+
+        map.apply_post_processing(config, rng);
+
+        map
+    }
+
+    //Function to apply the meta-steps shared by every algorithm to a generated map
+    pub fn apply_post_processing(&mut self, config : &MapConfig, rng : &mut RandomNumberGenerator) {
+        //Scatter special terrain over the generated layout
+        self.apply_terrain_features(&config.terrain, rng);
+
+        //Carve rivers and lakes through the level
+        self.apply_water_features(&config.water, rng);
+    }
+
+    //Function to create a new map with square rooms and corridors
+    pub fn new_map_rooms_and_corridors(new_depth : i32, width: i32, height: i32, config : &RoomsAndCorridorsConfig, rng : &mut RandomNumberGenerator) -> Map {
+        //Create a map of solid wall to carve out
+        let mut map = Map::new(new_depth, width, height);
 
         //Apply random rooms to the map
-        for _ in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
+        for _ in 0..config.max_rooms {
+            let w = rng.range(config.min_size, config.max_size);
+            let h = rng.range(config.min_size, config.max_size);
             let x = rng.roll_dice(1, width - w - 1) - 1;
             let y = rng.roll_dice(1, height - h - 1) - 1;
             let new_room = Rect::new(x, y, w, h);
@@ -183,47 +374,34 @@ impl Map {
     }
 
     //Function to create a new map with cellular automata
-    pub fn new_map_cellular_automata(new_depth : i32, width: i32, height: i32) -> Map {
-        //Calculate the total number of tiles in the map based on its width and height
-        let map_tile_count = (width * height) as usize;
-
-        //Create the map struct
-        let mut map = Map {
-            tiles : vec![TileType::Wall; map_tile_count],
-            rooms : Vec::new(),
-            width,
-            height,
-            depth: new_depth,
-            starting_position_x: 0,
-            starting_position_y: 0,
-            revealed_tiles : vec![false; map_tile_count],
-            visible_tiles : vec![false; map_tile_count]
-        };
+    pub fn new_map_cellular_automata(new_depth : i32, width: i32, height: i32, config : &CellularAutomataConfig, rng : &mut RandomNumberGenerator) -> Map {
+        //Create a map of solid wall to carve out
+        let mut map = Map::new(new_depth, width, height);
     
         //Generate the map
-        map.generate_cellular_automata();
+        map.generate_cellular_automata(config, rng);
 
         //Return the newly generated map
         map
     }
 
-    //Function to apply cellular automata to the map
-    fn generate_cellular_automata(&mut self) {
-        //Set the map's generation properties
-        const ITERATIONS : i32 = 10;
-        const BIRTH_LIMIT : i32 = 4;
-        const DEATH_LIMIT : i32 = 3;
-        const INITIAL_CHANCE : i32 = 35;
-        
-        //Create a new random number generator
-        let mut rng = RandomNumberGenerator::new();
+    //Function to apply cellular automata to the map, running them again until they make a cave with somewhere to start and somewhere else for the stairs
+    fn generate_cellular_automata(&mut self, config : &CellularAutomataConfig, rng : &mut RandomNumberGenerator) {
+        for _ in 0..MAX_CAVE_ATTEMPTS {
+            self.carve_cellular_automata(config, rng);
+            if self.place_cave_start_and_stairs() { return; }
+        }
+        panic!("Unable to generate a cave with room for the start and the stairs in {} attempts, check the cellular automata config", MAX_CAVE_ATTEMPTS);
+    }
 
+    //Function to carve a cave out of the map with cellular automata
+    fn carve_cellular_automata(&mut self, config : &CellularAutomataConfig, rng : &mut RandomNumberGenerator) {
         //Completely randomize the map
         for y in 1..self.height-1 {
             for x in 1..self.width-1 {
                 let roll = rng.roll_dice(1, 100);
                 let idx = self.xy_idx(x, y);
-                if roll > INITIAL_CHANCE {
+                if roll > config.initial_chance {
                     self.tiles[idx] = TileType::Floor 
                 }else { 
                     self.tiles[idx] = TileType::Wall
@@ -232,7 +410,7 @@ impl Map {
         }
 
         //Iteratively apply cellular automata rules
-        for _i in 0..ITERATIONS { //Iterate the algorithm
+        for _i in 0..config.iterations { //Iterate the algorithm
             let mut newtiles = self.tiles.clone();
 
             //Iterate through the tile map (excluding the borders)
@@ -241,24 +419,16 @@ impl Map {
                     let idx = self.xy_idx(x, y); //Get the index of this tile
 
                     //Calculate the number of wall neighbors to this tile
-                    let mut neighbors = 0;
-                    if self.tiles[idx - 1] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx + 1] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx - self.width as usize] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx + self.width as usize] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx - (self.width as usize - 1)] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx - (self.width as usize + 1)] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx + (self.width as usize - 1)] == TileType::Wall { neighbors += 1; }
-                    if self.tiles[idx + (self.width as usize + 1)] == TileType::Wall { neighbors += 1; }
+                    let neighbors = self.neighbors(x, y).filter(|(nx, ny)| self.tiles[self.xy_idx(*nx, *ny)] == TileType::Wall).count() as i32;
 
                     if self.tiles[idx] == TileType::Wall {
-                        if neighbors < DEATH_LIMIT {
+                        if neighbors < config.death_limit {
                             newtiles[idx] = TileType::Floor; //Wall didn't have enough neighbors, remove it
                         }else {
                             newtiles[idx] = TileType::Wall;
                         }
                     }else {
-                        if neighbors > BIRTH_LIMIT {
+                        if neighbors > config.birth_limit {
                             newtiles[idx] = TileType::Wall; //Floor had enough neighbors, make it a wall
                         }else {
                             newtiles[idx] = TileType::Floor;
@@ -270,23 +440,34 @@ impl Map {
             //Update the map's tiles
             self.tiles = newtiles.clone();
         }
+    }
 
-        //Find a starting point; start at the middle and walk left until we find an open tile
+    //Function to pick the start of a carved cave, wall off what can't be reached from it and put the stairs as far away as possible, returning false if the cave has no floor to start on or nowhere else for the stairs
+    fn place_cave_start_and_stairs(&mut self) -> bool {
+        //Find a starting point; start at the middle and walk left until we find an open tile, or take the first open tile if there's none that way
         self.starting_position_x = self.width / 2;
         self.starting_position_y = self.height / 2;
-        let mut start_idx = self.xy_idx(self.starting_position_x, self.starting_position_y);
-        while self.tiles[start_idx] != TileType::Floor {
+        while let Some(idx) = self.try_idx(self.starting_position_x, self.starting_position_y) {
+            if self.tiles[idx] == TileType::Floor { break; }
             self.starting_position_x -= 1;
-            start_idx = self.xy_idx(self.starting_position_x, self.starting_position_y);
         }
+        if !self.in_bounds(self.starting_position_x, self.starting_position_y) {
+            let first_floor = match self.tiles.iter().position(|tile| *tile == TileType::Floor) {
+                Some(first_floor) => first_floor,
+                None => return false
+            };
+            let (x, y) = self.idx_xy(first_floor);
+            self.starting_position_x = x;
+            self.starting_position_y = y;
+        }
+        let start_idx = self.start_idx();
 
         //Use a dijkstra map to find all the tiles we cannot reach from the starting point and fill them + find a viable location for the exit stairs
-        let map_starts : Vec<usize> = vec![start_idx]; //Set the starting position for the dijkstra map
-        let dijkstra_map = rltk::DijkstraMap::new(self.width, self.height, &map_starts , self, 200.0); //Create the dijkstra map
+        let distances = self.distances_from(start_idx);
         let mut exit_tile = (0, 0.0f32); //Create a tuple representing the exit position that will be searched for (tile index of the exit, distance from the start tile to the exit tile)
         for (i, tile) in self.tiles.iter_mut().enumerate() { //Iterate through all the tiles in the map
             if *tile == TileType::Floor {
-                let distance_to_start = dijkstra_map.map[i]; //Get the distance from this tile to the start position from the dijkstra map
+                let distance_to_start = distances[i]; //Get the distance from this tile to the start position from the dijkstra map
                 if distance_to_start == std::f32::MAX { //This tile is inaccessible (Flagged by a max value f32) so turn it into a wall
                     *tile = TileType::Wall;
                 } else {
@@ -299,7 +480,16 @@ impl Map {
             }
         }
 
-        //Place the down stairs at the viable exit that was found
+        //Place the down stairs at the viable exit that was found, if any floor other than the start could be reached
+        if exit_tile.1 == 0.0 { return false; }
         self.tiles[exit_tile.0] = TileType::DownStairs;
+        true
+    }
+
+    //Function to get the distance of every tile from the given start tile using a dijkstra map, with unreachable tiles flagged by a max value f32
+    pub fn distances_from(&self, start_idx : usize) -> Vec<f32> {
+        let map_starts : Vec<usize> = vec![start_idx]; //Set the starting position for the dijkstra map
+        let max_depth = (self.width * self.height) as f32; //Large enough that no reachable tile on a map of any size is cut off
+        rltk::DijkstraMap::new(self.width, self.height, &map_starts , self, max_depth).map
     }
 }
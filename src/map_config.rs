@@ -0,0 +1,277 @@
+use serde::{Serialize, Deserialize};
+use std::cmp::min;
+
+//Path of the data file the map generation parameters are loaded from
+pub const MAP_CONFIG_PATH : &str = "resources/map_config.json";
+
+//Smallest width and height a map can have, leaving room for the border and the terrain features placed inside it
+const MIN_MAP_SIZE : i32 = 10;
+
+//Generation parameters for the rooms and corridors algorithm
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct RoomsAndCorridorsConfig {
+    pub max_rooms : i32,
+    pub min_size : i32,
+    pub max_size : i32,
+    pub max_rooms_per_depth : i32, //Extra room placement attempts added for every level below the first
+    pub max_rooms_cap : i32
+}
+
+impl Default for RoomsAndCorridorsConfig {
+    fn default() -> RoomsAndCorridorsConfig {
+        RoomsAndCorridorsConfig {
+            max_rooms : 100,
+            min_size : 10,
+            max_size : 18,
+            max_rooms_per_depth : 10,
+            max_rooms_cap : 200
+        }
+    }
+}
+
+impl RoomsAndCorridorsConfig {
+    //Function to get a copy of this config with its values scaled for the given depth
+    pub fn for_depth(&self, depth : i32) -> RoomsAndCorridorsConfig {
+        let mut config = *self;
+        config.max_rooms = min(self.max_rooms_cap, self.max_rooms + self.max_rooms_per_depth * (depth - 1));
+        config
+    }
+}
+
+//Generation parameters for the cellular automata algorithm
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct CellularAutomataConfig {
+    pub iterations : i32,
+    pub birth_limit : i32,
+    pub death_limit : i32,
+    pub initial_chance : i32,
+    pub initial_chance_per_depth : i32, //Extra chance of a tile starting as a wall for every level below the first
+    pub initial_chance_cap : i32
+}
+
+impl Default for CellularAutomataConfig {
+    fn default() -> CellularAutomataConfig {
+        CellularAutomataConfig {
+            iterations : 10,
+            birth_limit : 4,
+            death_limit : 3,
+            initial_chance : 35,
+            initial_chance_per_depth : 1,
+            initial_chance_cap : 45
+        }
+    }
+}
+
+impl CellularAutomataConfig {
+    //Function to get a copy of this config with its values scaled for the given depth
+    pub fn for_depth(&self, depth : i32) -> CellularAutomataConfig {
+        let mut config = *self;
+        config.initial_chance = min(self.initial_chance_cap, self.initial_chance + self.initial_chance_per_depth * (depth - 1));
+        config
+    }
+}
+
+//Generation parameters for the special terrain scattered over every map
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct TerrainConfig {
+    pub rubble_patches : i32, //Number of rubble patches per 10,000 tiles
+    pub rubble_patch_size : i32,
+    pub lava_vents : i32, //Number of lava vent placement attempts per 10,000 tiles
+    pub lava_min_depth : i32
+}
+
+impl Default for TerrainConfig {
+    fn default() -> TerrainConfig {
+        TerrainConfig {
+            rubble_patches : 12,
+            rubble_patch_size : 8,
+            lava_vents : 10,
+            lava_min_depth : 3
+        }
+    }
+}
+
+//Generation parameters for the rivers and lakes carved through every map
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct WaterConfig {
+    pub river_chance : i32, //Percent chance of a river crossing the level
+    pub river_bank_width : i32,
+    pub lake_chance : i32, //Percent chance of the level having lakes
+    pub max_lakes : i32,
+    pub lake_min_radius : i32,
+    pub lake_max_radius : i32
+}
+
+impl Default for WaterConfig {
+    fn default() -> WaterConfig {
+        WaterConfig {
+            river_chance : 40,
+            river_bank_width : 1,
+            lake_chance : 50,
+            max_lakes : 3,
+            lake_min_radius : 2,
+            lake_max_radius : 5
+        }
+    }
+}
+
+//Generation parameters shared by every algorithm, plus the parameters of each algorithm; the defaults match the shipped data file, and keep every level at the original 100x100 unless a config sets size_per_depth
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct MapConfig {
+    pub width : i32,
+    pub height : i32,
+    pub size_per_depth : i32, //Extra width and height added for every level below the first
+    pub max_width : i32,
+    pub max_height : i32,
+    pub cellular_automata_chance : i32, //Percent chance of a level being generated with cellular automata instead of rooms and corridors
+    pub final_depth : i32, //Deepest level of the dungeon; taking its stairs wins the run
+    pub rooms_and_corridors : RoomsAndCorridorsConfig,
+    pub cellular_automata : CellularAutomataConfig,
+    pub terrain : TerrainConfig,
+    pub water : WaterConfig
+}
+
+impl Default for MapConfig {
+    fn default() -> MapConfig {
+        MapConfig {
+            width : 100,
+            height : 100,
+            size_per_depth : 0,
+            max_width : 160,
+            max_height : 160,
+            cellular_automata_chance : 33,
+            final_depth : 10,
+            rooms_and_corridors : RoomsAndCorridorsConfig::default(),
+            cellular_automata : CellularAutomataConfig::default(),
+            terrain : TerrainConfig::default(),
+            water : WaterConfig::default()
+        }
+    }
+}
+
+impl MapConfig {
+    //Function to load the map generation parameters from a data file, using the defaults if there is no file and stopping with a clear message if it can't be read, parsed or holds values the generators can't work with
+    pub fn load(path : &str) -> MapConfig {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return MapConfig::default(),
+            Err(err) => panic!("Unable to read map config {}: {}", path, err)
+        };
+        let config : MapConfig = serde_json::from_str(&data).unwrap_or_else(|err| panic!("Unable to parse map config {}: {}", path, err));
+        if let Err(problem) = config.validate() {
+            panic!("Invalid map config {}: {}", path, problem);
+        }
+        config
+    }
+
+    //Function to check that the parameters can generate a map at every depth, describing the first problem found
+    pub fn validate(&self) -> Result<(), String> {
+        let rooms = &self.rooms_and_corridors;
+        let water = &self.water;
+        let checks = [
+            (self.width >= MIN_MAP_SIZE && self.height >= MIN_MAP_SIZE, format!("width and height must be at least {}", MIN_MAP_SIZE)),
+            (self.max_width >= self.width && self.max_height >= self.height, "max_width and max_height can't be less than width and height".to_string()),
+            (self.size_per_depth >= 0, "size_per_depth can't be negative".to_string()),
+            (self.final_depth >= 1, "final_depth must be at least 1".to_string()),
+            (rooms.max_rooms >= 1 && rooms.max_rooms_cap >= 1, "max_rooms and max_rooms_cap must be at least 1".to_string()),
+            (rooms.max_rooms_per_depth >= 0, "max_rooms_per_depth can't be negative".to_string()),
+            (rooms.min_size >= 1 && rooms.min_size < rooms.max_size, "min_size must be at least 1 and less than max_size".to_string()),
+            (rooms.max_size < i32::min(self.width, self.height), "max_size must be less than the map's width and height".to_string()),
+            (self.cellular_automata.iterations >= 0, "iterations can't be negative".to_string()),
+            (self.terrain.rubble_patch_size >= 0, "rubble_patch_size can't be negative".to_string()),
+            (water.river_bank_width >= 0, "river_bank_width can't be negative".to_string()),
+            (water.max_lakes >= 1, "max_lakes must be at least 1".to_string()),
+            (water.lake_min_radius >= 0 && water.lake_min_radius <= water.lake_max_radius, "lake_min_radius can't be negative or more than lake_max_radius".to_string())
+        ];
+
+        match checks.iter().find(|(valid, _problem)| !valid) {
+            Some((_valid, problem)) => Err(problem.clone()),
+            None => Ok(())
+        }
+    }
+
+    //Function to get the dimensions of a map at the given depth
+    pub fn dimensions_for_depth(&self, depth : i32) -> (i32, i32) {
+        let growth = self.size_per_depth * (depth - 1);
+        (min(self.max_width, self.width + growth), min(self.max_height, self.height + growth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Function to check that a change to the default config is rejected with a problem mentioning the given text
+    fn assert_rejected(change : impl Fn(&mut MapConfig), mentions : &str) {
+        let mut config = MapConfig::default();
+        change(&mut config);
+        match config.validate() {
+            Err(problem) => assert!(problem.contains(mentions), "\"{}\" doesn't mention {}", problem, mentions),
+            Ok(()) => panic!("Config with bad {} was accepted", mentions)
+        }
+    }
+
+    #[test]
+    fn default_matches_shipped_file() {
+        let shipped : MapConfig = serde_json::from_str(include_str!("../resources/map_config.json")).expect("Unable to parse the shipped map config");
+        let to_json = |config : &MapConfig| serde_json::to_value(config).expect("Unable to serialize map config");
+        assert_eq!(to_json(&shipped), to_json(&MapConfig::default()));
+        assert_eq!(shipped.validate(), Ok(()));
+    }
+
+    #[test]
+    fn default_keeps_the_original_map_size_at_every_depth() {
+        let config = MapConfig::default();
+        assert_eq!(config.dimensions_for_depth(1), (100, 100));
+        assert_eq!(config.dimensions_for_depth(config.final_depth), (100, 100));
+    }
+
+    #[test]
+    fn rejects_each_bad_value() {
+        assert_rejected(|c| c.width = MIN_MAP_SIZE - 1, "width and height");
+        assert_rejected(|c| c.height = MIN_MAP_SIZE - 1, "width and height");
+        assert_rejected(|c| c.max_width = c.width - 1, "max_width");
+        assert_rejected(|c| c.max_height = c.height - 1, "max_width");
+        assert_rejected(|c| c.size_per_depth = -1, "size_per_depth");
+        assert_rejected(|c| c.final_depth = 0, "final_depth");
+        assert_rejected(|c| c.rooms_and_corridors.max_rooms = 0, "max_rooms");
+        assert_rejected(|c| c.rooms_and_corridors.max_rooms_cap = 0, "max_rooms");
+        assert_rejected(|c| c.rooms_and_corridors.max_rooms_per_depth = -1, "max_rooms_per_depth");
+        assert_rejected(|c| c.rooms_and_corridors.min_size = 0, "min_size");
+        assert_rejected(|c| c.rooms_and_corridors.min_size = c.rooms_and_corridors.max_size, "min_size");
+        assert_rejected(|c| c.rooms_and_corridors.max_size = c.width, "max_size");
+        assert_rejected(|c| c.cellular_automata.iterations = -1, "iterations");
+        assert_rejected(|c| c.terrain.rubble_patch_size = -1, "rubble_patch_size");
+        assert_rejected(|c| c.water.river_bank_width = -1, "river_bank_width");
+        assert_rejected(|c| c.water.max_lakes = 0, "max_lakes");
+        assert_rejected(|c| c.water.lake_min_radius = -1, "lake_min_radius");
+        assert_rejected(|c| c.water.lake_min_radius = c.water.lake_max_radius + 1, "lake_min_radius");
+    }
+
+    #[test]
+    fn for_depth_scales_up_to_the_cap() {
+        let rooms = RoomsAndCorridorsConfig{ max_rooms : 100, max_rooms_per_depth : 10, max_rooms_cap : 150, ..RoomsAndCorridorsConfig::default() };
+        assert_eq!(rooms.for_depth(1).max_rooms, 100);
+        assert_eq!(rooms.for_depth(3).max_rooms, 120);
+        assert_eq!(rooms.for_depth(20).max_rooms, 150);
+
+        let caves = CellularAutomataConfig{ initial_chance : 35, initial_chance_per_depth : 2, initial_chance_cap : 40, ..CellularAutomataConfig::default() };
+        assert_eq!(caves.for_depth(1).initial_chance, 35);
+        assert_eq!(caves.for_depth(2).initial_chance, 37);
+        assert_eq!(caves.for_depth(10).initial_chance, 40);
+    }
+
+    #[test]
+    fn dimensions_grow_with_depth_up_to_the_maximum() {
+        let config = MapConfig{ width : 100, height : 80, size_per_depth : 4, max_width : 120, max_height : 120, ..MapConfig::default() };
+        assert_eq!(config.dimensions_for_depth(1), (100, 80));
+        assert_eq!(config.dimensions_for_depth(3), (108, 88));
+        assert_eq!(config.dimensions_for_depth(10), (120, 116));
+        assert_eq!(config.dimensions_for_depth(30), (120, 120));
+    }
+}
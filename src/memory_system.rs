@@ -0,0 +1,41 @@
+use specs::prelude::*;
+use super::{Map, Position, Renderable, Memorable, TileSize, RememberedEntity};
+
+pub struct MemorySystem {}
+
+impl<'a> System<'a> for MemorySystem {
+    type SystemData = (WriteExpect<'a, Map>, ReadStorage<'a, Position>, ReadStorage<'a, Renderable>, ReadStorage<'a, Memorable>, ReadStorage<'a, TileSize>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        //Get the necessary data from the ECS
+        let (mut map, positions, renderables, memorables, sizes) = data;
+        let map = &mut *map;
+
+        //Forget what was on the tiles the player can see, as they can see what is there now
+        for idx in map.visible_tiles.iter() {
+            map.remembered_entities[idx] = None;
+        }
+
+        //Remember the static entities on visible tiles, keeping whichever is drawn on top where they share a tile
+        for (pos, render, _memorable, size) in (&positions, &renderables, &memorables, sizes.maybe()).join() {
+            let (width, height) = size.map(|size| (size.x, size.y)).unwrap_or((1, 1));
+            for ty in pos.y .. pos.y + height {
+                for tx in pos.x .. pos.x + width {
+                    let idx = match map.try_idx(tx, ty) {
+                        Some(idx) => idx,
+                        None => continue
+                    };
+                    if !map.visible_tiles[idx] { continue; }
+
+                    let on_top = match map.remembered_entities[idx] {
+                        Some(remembered) => render.render_order <= remembered.render_order,
+                        None => true
+                    };
+                    if on_top {
+                        map.remembered_entities[idx] = Some(RememberedEntity{ glyph : render.glyph, fg : render.fg, render_order : render.render_order });
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use specs::prelude::*;
+use rltk::{Rltk, RGB, Point};
+use super::{Map, TileType, Keymap, KeyAction};
+
+//Size and position of the always-on minimap widget in the top right corner of the screen
+const MINIMAP_WIDTH : i32 = 20;
+const MINIMAP_HEIGHT : i32 = 12;
+
+//Function to draw the whole map downscaled to fill the screen above the HUD
+pub fn draw_overview(ecs: &World, ctx : &mut Rltk) {
+    let (screen_width, screen_height) = ctx.get_char_size();
+    draw_scaled_map(ecs, ctx, 0, 0, screen_width as i32, screen_height as i32 - 2);
+    let prompt = match ecs.fetch::<Keymap>().key_name(KeyAction::ToggleOverview) {
+        Some(key_name) => format!("Overview - press {} or Escape to return", key_name),
+        None => "Overview - press Escape to return".to_string()
+    };
+    ctx.print_color(1, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &prompt);
+}
+
+//Function to draw the minimap widget in the corner of the screen
+pub fn draw_minimap(ecs: &World, ctx : &mut Rltk) {
+    let (screen_width, _screen_height) = ctx.get_char_size();
+    let x = screen_width as i32 - MINIMAP_WIDTH - 2;
+    ctx.draw_box(x, 0, MINIMAP_WIDTH + 1, MINIMAP_HEIGHT + 1, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK));
+    draw_scaled_map(ecs, ctx, x + 1, 1, MINIMAP_WIDTH, MINIMAP_HEIGHT);
+}
+
+//Function to draw the revealed parts of the map shrunk to fit in the given area, with each cell summarising a block of tiles
+fn draw_scaled_map(ecs: &World, ctx : &mut Rltk, x : i32, y : i32, width : i32, height : i32) {
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+
+    //Use the smallest square block size that fits the whole map
+    let scale = i32::max((map.width + width - 1) / width, (map.height + height - 1) / height).max(1);
+
+    for cy in 0..height {
+        for cx in 0..width {
+            //Find the most important thing in this cell's block of tiles
+            let (mut has_player, mut has_stairs, mut has_poi, mut has_water, mut has_floor, mut has_wall) = (false, false, false, false, false, false);
+            for ty in cy * scale .. (cy + 1) * scale {
+                for tx in cx * scale .. (cx + 1) * scale {
+                    let idx = match map.try_idx(tx, ty) {
+                        Some(idx) => idx,
+                        None => continue
+                    };
+                    if tx == player_pos.x && ty == player_pos.y { has_player = true; }
+                    if !map.revealed_tiles[idx] { continue; } //Only show revealed tiles
+                    //Points of interest are the things the player has seen and remembers, so they stay marked once they're out of view
+                    if map.remembered_entities[idx].is_some() { has_poi = true; }
+                    match map.tiles[idx] {
+                        TileType::DownStairs => has_stairs = true,
+                        TileType::DeepWater | TileType::ShallowWater => has_water = true,
+                        TileType::Wall => has_wall = true,
+                        _ => has_floor = true
+                    }
+                }
+            }
+
+            let (glyph, fg) = if has_player {
+                ('@', RGB::named(rltk::YELLOW))
+            } else if has_stairs {
+                ('>', RGB::named(rltk::WHITE))
+            } else if has_poi {
+                ('!', RGB::named(rltk::MAGENTA))
+            } else if has_water {
+                ('≈', RGB::from_u8(20, 60, 200))
+            } else if has_floor {
+                ('.', RGB::from_u8(80, 55, 10))
+            } else if has_wall {
+                ('#', RGB::from_u8(160, 110, 20))
+            } else {
+                (' ', RGB::named(rltk::BLACK))
+            };
+            ctx.set(x + cx, y + cy, fg, RGB::named(rltk::BLACK), rltk::to_cp437(glyph));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use specs::prelude::*;
+use rltk::{Rltk, RGB};
+use super::{Position, Renderable, ParticleLifetime};
+
+//A particle waiting to be created by the spawn system
+struct ParticleRequest {
+    x : i32,
+    y : i32,
+    fg : RGB,
+    bg : RGB,
+    glyph : rltk::FontCharType,
+    lifetime_ms : f32
+}
+
+//Resource collecting requests for particles from anywhere in the game, so they can be created in one place
+#[derive(Default)]
+pub struct ParticleBuilder {
+    requests : Vec<ParticleRequest>
+}
+
+impl ParticleBuilder {
+    //Function to request a particle at a map position that lasts for the given number of milliseconds
+    pub fn request(&mut self, x : i32, y : i32, fg : RGB, bg : RGB, glyph : rltk::FontCharType, lifetime_ms : f32) {
+        self.requests.push(ParticleRequest{ x, y, fg, bg, glyph, lifetime_ms });
+    }
+
+    //Function to request a brief flash on a tile, like when something is hit
+    pub fn flash(&mut self, x : i32, y : i32, fg : RGB) {
+        self.request(x, y, fg, RGB::named(rltk::BLACK), rltk::to_cp437('*'), 150.0);
+    }
+
+    //Function to request a fade in around a position, covering it with shade that clears from the center outwards
+    pub fn fade_in(&mut self, x : i32, y : i32, radius : i32) {
+        for py in y - radius ..= y + radius {
+            for px in x - radius ..= x + radius {
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(rltk::Point::new(x, y), rltk::Point::new(px, py));
+                if distance <= radius as f32 {
+                    self.request(px, py, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), rltk::to_cp437('▒'), 200.0 + distance * 80.0);
+                }
+            }
+        }
+    }
+}
+
+pub struct ParticleSpawnSystem {}
+
+impl<'a> System<'a> for ParticleSpawnSystem {
+    type SystemData = (Entities<'a>, WriteStorage<'a, Position>, WriteStorage<'a, Renderable>, WriteStorage<'a, ParticleLifetime>, WriteExpect<'a, ParticleBuilder>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        //Get the necessary data from the ECS
+        let (entities, mut positions, mut renderables, mut particles, mut particle_builder) = data;
+
+        //Create a particle entity for every request
+        for request in particle_builder.requests.iter() {
+            let particle = entities.create();
+            positions.insert(particle, Position{ x : request.x, y : request.y }).expect("Unable to insert position");
+            renderables.insert(particle, Renderable{ glyph : request.glyph, fg : request.fg, bg : request.bg, render_order : 0 }).expect("Unable to insert renderable");
+            particles.insert(particle, ParticleLifetime{ lifetime_ms : request.lifetime_ms, initial_lifetime_ms : request.lifetime_ms }).expect("Unable to insert lifetime");
+        }
+        particle_builder.requests.clear();
+    }
+}
+
+//Function to age every particle by the time the last frame took, and delete the ones that have expired
+pub fn cull_dead_particles(ecs : &mut World, ctx : &Rltk) {
+    let mut dead_particles : Vec<Entity> = Vec::new();
+    {
+        let entities = ecs.entities();
+        let mut particles = ecs.write_storage::<ParticleLifetime>();
+        for (entity, particle) in (&entities, &mut particles).join() {
+            particle.lifetime_ms -= ctx.frame_time_ms;
+            if particle.lifetime_ms < 0.0 {
+                dead_particles.push(entity);
+            }
+        }
+    }
+    ecs.delete_entities(&dead_particles).expect("Unable to delete particles");
+}
@@ -1,20 +1,24 @@
-use rltk::{VirtualKeyCode, Rltk, Point};
+use rltk::{VirtualKeyCode, Rltk, Point, RGB};
 use specs::prelude::*;
-use super::{Position, Player, TileType, Map, State, RunState, Viewshed};
+use super::{Position, Player, TileType, Map, State, RunState, Viewshed, Shrine, use_shrine, ProvidesFood, eat_food, ParticleBuilder, Keymap, KeyAction, Command, Travel, start_travel, start_travel_to_stairs, start_explore, next_travel_command, camera, CameraSettings, RenderMode};
 use std::cmp::{min, max};
 
-//Function to move the player entity
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+//Function to move the player entity, returning true if they moved
+pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> bool {
     let mut positions = ecs.write_storage::<Position>(); //Get write access to the ECS's Position component storage
     let mut players = ecs.write_storage::<Player>(); //Get write access to the ECS's Player component storage
     let mut viewsheds = ecs.write_storage::<Viewshed>(); //Get write access to the ECS's Viewshed component storage
     let map = ecs.fetch::<Map>(); //Fetch the map from the ECS
 
+    let mut moved = false;
     for (_player, pos, viewshed) in (&mut players, &mut positions, &mut viewsheds).join() { //All entities with both a Player component and a Position component
-        let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
-        if map.tiles[destination_idx] != TileType::Wall {
-            pos.x = min(map.width - 1 , max(0, pos.x + delta_x));
-            pos.y = min(map.height - 1, max(0, pos.y + delta_y));
+        let destination_idx = match map.try_idx(pos.x + delta_x, pos.y + delta_y) {
+            Some(idx) => idx,
+            None => continue //Can't walk off the edge of the map
+        };
+        if map.tiles[destination_idx].is_walkable() {
+            pos.x += delta_x;
+            pos.y += delta_y;
 
             //Update the Point tracking the player's position
             let mut ppos = ecs.write_resource::<Point>();
@@ -23,55 +27,190 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
 
             //Tell the viewshed it needs to be updated
             viewshed.dirty = true;
+            moved = true;
+        } else {
+            //Flash the tile the player bumped in to
+            let mut particle_builder = ecs.write_resource::<ParticleBuilder>();
+            particle_builder.flash(pos.x + delta_x, pos.y + delta_y, RGB::named(rltk::ORANGE));
         }
     }
+    moved
 }
 
 //Function to attempt interacting with an object on the map
 pub fn attempt_interact(ecs: &mut World) -> RunState {
+    //Use a shrine if the player is standing on one
+    let shrine = {
+        let player_pos = ecs.fetch::<Point>();
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let shrines = ecs.read_storage::<Shrine>();
+        (&entities, &positions, &shrines).join()
+            .find(|(_entity, pos, _shrine)| pos.x == player_pos.x && pos.y == player_pos.y)
+            .map(|(entity, _pos, _shrine)| entity)
+    };
+    if let Some(shrine) = shrine {
+        return use_shrine(ecs, shrine);
+    }
+
+    //Eat any food the player is standing on
+    let food = {
+        let player_pos = ecs.fetch::<Point>();
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let food = ecs.read_storage::<ProvidesFood>();
+        (&entities, &positions, &food).join()
+            .find(|(_entity, pos, _food)| pos.x == player_pos.x && pos.y == player_pos.y)
+            .map(|(entity, _pos, _food)| entity)
+    };
+    if let Some(food) = food {
+        return eat_food(ecs, food);
+    }
+
+    //Interacting with a down stairs descends it, otherwise there is no interactable object at the player's location
+    attempt_descend(ecs)
+}
+
+//Function to attempt going down a staircase at the player's location
+pub fn attempt_descend(ecs: &mut World) -> RunState {
     let player_pos = ecs.fetch::<Point>(); //Get the Point tracking the player's position
     let map = ecs.fetch::<Map>(); //Fetch the current map from the ECS
-    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
-    if map.tiles[player_idx] == TileType::DownStairs {
-        //If the player interacts with a down stairs, move to the next map level
+    let on_stairs = matches!(map.try_idx(player_pos.x, player_pos.y), Some(idx) if map.tiles[idx] == TileType::DownStairs);
+    if on_stairs {
+        //If the player is on a down stairs, move to the next map level
         RunState::NextLevel
     } else {
-        //If there is no interactable object at the player's location, do nothing
+        //If there are no stairs at the player's location, do nothing
         RunState::Paused
     }
 }
 
-//Function to read user input from RLTK
-pub fn user_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    //Player movement
+//Function to read user input from RLTK and turn it in to a command
+pub fn user_input(gs: &mut State, ctx: &mut Rltk) -> Option<Command> {
+    //While travelling, any key press stops the player, otherwise they take the next step
+    if gs.ecs.fetch::<Travel>().is_active() {
+        if ctx.key.is_some() {
+            gs.ecs.write_resource::<Travel>().stop();
+            return None;
+        }
+        return next_travel_command(&mut gs.ecs);
+    }
+
+    //Clicking a revealed tile sets off towards it
+    if ctx.left_click {
+        let mouse_pos = ctx.mouse_pos();
+        let target = camera::screen_to_map(&gs.ecs, ctx, mouse_pos);
+        let target_idx = {
+            let map = gs.ecs.fetch::<Map>();
+            map.try_idx(target.x, target.y).filter(|idx| map.revealed_tiles[*idx])
+        };
+        if let Some(target_idx) = target_idx {
+            if start_travel(&mut gs.ecs, target_idx) {
+                return next_travel_command(&mut gs.ecs);
+            }
+        }
+    }
+
+    //Look up the action bound to the pressed key
+    let action = match ctx.key {
+        None => { return None } //Nothing happened
+        Some(key) => gs.ecs.fetch::<Keymap>().action(key)
+    };
+
+    match action? { //Key isn't bound to anything
+        //Player movement
+        KeyAction::MoveLeft => Some(Command::Move{ dx: -1, dy: 0 }),
+        KeyAction::MoveRight => Some(Command::Move{ dx: 1, dy: 0 }),
+        KeyAction::MoveUp => Some(Command::Move{ dx: 0, dy: -1 }),
+        KeyAction::MoveDown => Some(Command::Move{ dx: 0, dy: 1 }),
+        KeyAction::MoveUpLeft => Some(Command::Move{ dx: -1, dy: -1 }),
+        KeyAction::MoveUpRight => Some(Command::Move{ dx: 1, dy: -1 }),
+        KeyAction::MoveDownLeft => Some(Command::Move{ dx: -1, dy: 1 }),
+        KeyAction::MoveDownRight => Some(Command::Move{ dx: 1, dy: 1 }),
+
+        KeyAction::Wait => Some(Command::Wait),
+        KeyAction::Interact => Some(Command::Interact),
+        KeyAction::Descend => Some(Command::Descend),
+
+        //Automatic travel
+        KeyAction::AutoExplore => {
+            start_explore(&mut gs.ecs);
+            next_travel_command(&mut gs.ecs)
+        }
+        KeyAction::TravelToStairs => {
+            if start_travel_to_stairs(&mut gs.ecs) {
+                next_travel_command(&mut gs.ecs)
+            } else {
+                None
+            }
+        }
+
+        //Interface
+        KeyAction::ToggleOverview => {
+            gs.runstate = RunState::Overview;
+            None
+        }
+        KeyAction::Look => {
+            let player_pos = *gs.ecs.fetch::<Point>();
+            gs.ecs.write_resource::<CameraSettings>().look_cursor = Some(player_pos);
+            gs.runstate = RunState::Look;
+            None
+        }
+        KeyAction::ToggleCameraClamp => {
+            let mut settings = gs.ecs.write_resource::<CameraSettings>();
+            settings.clamp_to_edges = !settings.clamp_to_edges;
+            None
+        }
+        KeyAction::ToggleBoundaries => {
+            let mut settings = gs.ecs.write_resource::<CameraSettings>();
+            settings.show_boundaries = !settings.show_boundaries;
+            None
+        }
+        KeyAction::ToggleRenderMode => {
+            let mut mode = gs.ecs.write_resource::<RenderMode>();
+            *mode = if *mode == RenderMode::Ascii { RenderMode::Tiles } else { RenderMode::Ascii };
+            None
+        }
+    }
+}
+
+//Function to read user input from RLTK while the map overview is open
+pub fn overview_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
     match ctx.key {
-        None => { return RunState::Paused } //Nothing happened
-        Some(key) => match key {
-            VirtualKeyCode::Left |
-            VirtualKeyCode::A |
-            VirtualKeyCode::Numpad4 |
-            VirtualKeyCode::H => try_move_player(-1, 0, &mut gs.ecs),
-
-            VirtualKeyCode::Right |
-            VirtualKeyCode::D |
-            VirtualKeyCode::Numpad6 |
-            VirtualKeyCode::L => try_move_player(1, 0, &mut gs.ecs),
-
-            VirtualKeyCode::Up |
-            VirtualKeyCode::W |
-            VirtualKeyCode::Numpad8 |
-            VirtualKeyCode::K => try_move_player(0, -1, &mut gs.ecs),
-
-            VirtualKeyCode::Down |
-            VirtualKeyCode::S |
-            VirtualKeyCode::Numpad2 |
-            VirtualKeyCode::J => try_move_player(0, 1, &mut gs.ecs),
-
-            VirtualKeyCode::E => {
-                return attempt_interact(&mut gs.ecs);
-            }            
-            _ => { return RunState::Paused }
-        },
+        None => RunState::Overview,
+        Some(VirtualKeyCode::Escape) => RunState::Paused,
+        Some(key) => {
+            if gs.ecs.fetch::<Keymap>().action(key) == Some(KeyAction::ToggleOverview) {
+                RunState::Paused
+            } else {
+                RunState::Overview
+            }
+        }
+    }
+}
+
+//Function to read user input from RLTK while in look mode, moving the cursor the camera follows
+pub fn look_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    let action = match ctx.key {
+        None => { return RunState::Look }
+        Some(VirtualKeyCode::Escape) => Some(KeyAction::Look),
+        Some(key) => gs.ecs.fetch::<Keymap>().action(key)
+    };
+
+    if action == Some(KeyAction::Look) {
+        //Leave look mode, putting the camera back on the player
+        gs.ecs.write_resource::<CameraSettings>().look_cursor = None;
+        return RunState::Paused;
+    }
+
+    //Move the cursor, keeping it on the map
+    if let Some((dx, dy)) = action.and_then(|action| action.direction()) {
+        let map = gs.ecs.fetch::<Map>();
+        let mut settings = gs.ecs.write_resource::<CameraSettings>();
+        if let Some(cursor) = settings.look_cursor.as_mut() {
+            cursor.x = min(map.width - 1, max(0, cursor.x + dx));
+            cursor.y = min(map.height - 1, max(0, cursor.y + dy));
+        }
     }
-    RunState::Running
+    RunState::Look
 }
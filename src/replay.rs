@@ -0,0 +1,201 @@
+use specs::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use super::{Command, TurnCounter, GameLog};
+
+//Path of the file every run is recorded to
+pub const REPLAY_PATH : &str = "replay.jsonl";
+
+//A command that was performed, and the turn it was performed on
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct RecordedCommand {
+    pub turn : u64,
+    pub command : Command
+}
+
+//A line of a replay file, which starts with the run's seed and has a line appended for every command performed and once the run is over
+#[derive(Serialize, Deserialize)]
+enum ReplayEntry {
+    Seed(u64),
+    Command(RecordedCommand),
+    Finished
+}
+
+//Everything needed to reproduce a run exactly: the seed of its random number generator and every command performed
+#[derive(Clone, Default)]
+pub struct Replay {
+    pub seed : u64,
+    pub commands : Vec<RecordedCommand>,
+    pub finished : bool //The run ended in death or victory, so it can't be continued
+}
+
+impl Replay {
+    //Function to load a replay from a file
+    pub fn load(path : &str) -> Replay {
+        let data = std::fs::read_to_string(path).expect("Unable to read replay");
+        Replay::parse(&data).unwrap_or_else(|err| panic!("Unable to parse replay {}: {}", path, err))
+    }
+
+    //Function to check if there is a saved run at the given path that can be continued
+    pub fn can_continue(path : &str) -> bool {
+        match std::fs::read_to_string(path) {
+            Ok(data) => Replay::parse(&data).map(|replay| !replay.finished).unwrap_or(false),
+            Err(_) => false
+        }
+    }
+
+    //Function to read a replay from the lines of a replay file
+    fn parse(data : &str) -> Result<Replay, String> {
+        let mut lines = data.lines().filter(|line| !line.trim().is_empty());
+        let seed = match lines.next().map(serde_json::from_str::<ReplayEntry>) {
+            Some(Ok(ReplayEntry::Seed(seed))) => seed,
+            _ => return Err("the first line must hold the seed".to_string())
+        };
+
+        let mut replay = Replay{ seed, commands : Vec::new(), finished : false };
+        for line in lines {
+            match serde_json::from_str::<ReplayEntry>(line).map_err(|err| err.to_string())? {
+                ReplayEntry::Command(recorded) => replay.commands.push(recorded),
+                ReplayEntry::Finished => replay.finished = true,
+                ReplayEntry::Seed(_) => return Err("only the first line can hold the seed".to_string())
+            }
+        }
+        Ok(replay)
+    }
+}
+
+//Resource recording the current run's commands, appending each one to the replay file as it happens so a crash doesn't lose them
+pub struct ReplayRecorder {
+    pub replay : Replay,
+    file : Option<File> //The file being recorded to, if the run is being saved
+}
+
+impl ReplayRecorder {
+    //Function to start recording a new run with the given seed, saving it to the given path if there is one
+    pub fn new(seed : u64, path : Option<&str>) -> ReplayRecorder {
+        let file = path.map(|path| File::create(path).expect("Unable to create replay"));
+        let mut recorder = ReplayRecorder{ replay : Replay{ seed, commands : Vec::new(), finished : false }, file };
+        recorder.append(&ReplayEntry::Seed(seed));
+        recorder
+    }
+
+    //Function to write the run recorded so far to the given path, replacing whatever was there, and keep saving to it from then on
+    pub fn save_to(&mut self, path : &str) {
+        self.file = Some(File::create(path).expect("Unable to create replay"));
+        self.append(&ReplayEntry::Seed(self.replay.seed));
+        for recorded in self.replay.commands.clone() {
+            self.append(&ReplayEntry::Command(recorded));
+        }
+        if self.replay.finished {
+            self.append(&ReplayEntry::Finished);
+        }
+    }
+
+    //Function to record a command and save it to the replay
+    pub fn record(&mut self, turn : u64, command : Command) {
+        let recorded = RecordedCommand{ turn, command };
+        self.replay.commands.push(recorded);
+        self.append(&ReplayEntry::Command(recorded));
+    }
+
+    //Function to mark the run as over and save that to the replay
+    pub fn finish(&mut self) {
+        self.replay.finished = true;
+        self.append(&ReplayEntry::Finished);
+    }
+
+    //Function to add a line to the end of the replay file, if the run is being saved
+    fn append(&mut self, entry : &ReplayEntry) {
+        if let Some(file) = self.file.as_mut() {
+            let line = serde_json::to_string(entry).expect("Unable to serialize replay");
+            writeln!(file, "{}", line).expect("Unable to write replay");
+        }
+    }
+}
+
+//Resource holding the commands of a replay that still have to be fed back in to the game loop
+#[derive(Default)]
+pub struct ReplayPlayback {
+    pub commands : VecDeque<RecordedCommand>
+}
+
+//Function to get the next command of the replay being played back, if there is one
+pub fn next_replay_command(ecs : &mut World) -> Option<Command> {
+    let turn = ecs.fetch::<TurnCounter>().turn;
+    let mut playback = ecs.write_resource::<ReplayPlayback>();
+    let recorded = playback.commands.pop_front()?;
+    if recorded.turn != turn {
+        //The run has drifted from the recording, so feeding it more commands would only make things worse
+        ecs.write_resource::<GameLog>().entries.push(format!("Replay desynchronised: command recorded on turn {} came up on turn {}, so the run carries on from here.", recorded.turn, turn));
+        playback.commands.clear();
+        return None;
+    }
+    Some(recorded.command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Function to get the turn and command of every recorded command, for comparing replays
+    fn commands_of(replay : &Replay) -> Vec<(u64, Command)> {
+        replay.commands.iter().map(|recorded| (recorded.turn, recorded.command)).collect()
+    }
+
+    #[test]
+    fn recorded_run_reads_back_and_continues() {
+        let path = std::env::temp_dir().join(format!("replay_round_trip_{}.jsonl", std::process::id()));
+        let path = path.to_str().expect("Temporary path isn't valid unicode");
+
+        //Record a few commands, as a run in progress would
+        let mut recorder = ReplayRecorder::new(42, Some(path));
+        recorder.record(0, Command::Move{ dx : 1, dy : -1 });
+        recorder.record(1, Command::Wait);
+        recorder.record(1, Command::Interact);
+        let unfinished = Replay::load(path);
+        assert_eq!(unfinished.seed, 42);
+        assert_eq!(commands_of(&unfinished), commands_of(&recorder.replay));
+        assert!(!unfinished.finished);
+        assert!(Replay::can_continue(path));
+
+        //Continue the run from the first two commands, as if the third no longer matched, and check the save is cut back to them
+        let mut recorder = ReplayRecorder::new(unfinished.seed, None);
+        recorder.record(0, Command::Move{ dx : 1, dy : -1 });
+        recorder.record(1, Command::Wait);
+        recorder.save_to(path);
+        let truncated = Replay::load(path);
+        assert_eq!(commands_of(&truncated), vec![(0, Command::Move{ dx : 1, dy : -1 }), (1, Command::Wait)]);
+        assert!(Replay::can_continue(path));
+
+        //Carry on playing, then end the run
+        recorder.record(2, Command::Descend);
+        recorder.finish();
+        let finished = Replay::load(path);
+        assert_eq!(finished.seed, 42);
+        assert_eq!(commands_of(&finished), vec![(0, Command::Move{ dx : 1, dy : -1 }), (1, Command::Wait), (2, Command::Descend)]);
+        assert!(finished.finished);
+        assert!(!Replay::can_continue(path));
+
+        std::fs::remove_file(path).expect("Unable to remove test replay");
+    }
+
+    #[test]
+    fn playback_only_run_writes_nothing() {
+        let mut recorder = ReplayRecorder::new(7, None);
+        recorder.record(0, Command::Wait);
+        recorder.finish();
+        assert_eq!(commands_of(&recorder.replay), vec![(0, Command::Wait)]);
+        assert!(recorder.replay.finished);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(Replay::parse("").is_err());
+        assert!(Replay::parse(r#"{"Command":{"turn":0,"command":"Wait"}}"#).is_err()); //No seed first
+        assert!(Replay::parse("{\"Seed\":1}\n{\"Seed\":2}").is_err());
+        assert!(Replay::parse("{\"Seed\":1}\nnot json").is_err());
+        assert!(Replay::parse("{\"Seed\":1}\n").is_ok());
+    }
+}
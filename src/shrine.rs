@@ -0,0 +1,35 @@
+use specs::prelude::*;
+use super::{Map, Shrine, ShrineEffect, Telepathy, GameLog, RunState, Travel};
+
+//How far and for how many turns a telepathy shrine lets the player sense monsters
+const TELEPATHY_RANGE : i32 = 30;
+const TELEPATHY_TURNS : i32 = 50;
+
+//Function to use a shrine, which crumbles once its effect is spent, returning the run state that follows
+pub fn use_shrine(ecs : &mut World, shrine_entity : Entity) -> RunState {
+    let effect = match ecs.read_storage::<Shrine>().get(shrine_entity) {
+        Some(shrine) => shrine.effect,
+        None => return RunState::Paused
+    };
+    ecs.delete_entity(shrine_entity).expect("Unable to delete entity");
+
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut log = ecs.write_resource::<GameLog>();
+    match effect {
+        ShrineEffect::MagicMapping => {
+            log.entries.push("The shrine shows you the layout of the level.".to_string());
+            RunState::MagicMapReveal{ row : 0 }
+        }
+        ShrineEffect::Telepathy => {
+            log.entries.push("You sense the minds around you.".to_string());
+            ecs.write_storage::<Telepathy>().insert(player_entity, Telepathy{ range : TELEPATHY_RANGE, turns : TELEPATHY_TURNS }).expect("Unable to insert telepathy");
+            RunState::Running
+        }
+        ShrineEffect::Amnesia => {
+            log.entries.push("Your memories of this level slip away.".to_string());
+            ecs.write_resource::<Map>().forget_unseen_tiles();
+            ecs.write_resource::<Travel>().stop(); //Any route being followed may run through tiles that are no longer known
+            RunState::Running
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use specs::prelude::*;
+use rltk::{RGB, RandomNumberGenerator};
+use super::{Map, TileType, Position, Renderable, Name, LightSource, TileSize, Memorable, Viewshed, Monster, Hidden, FovAlgorithm, Shrine, ShrineEffect, ProvidesFood};
+
+//Function to fill the current map with its features
+pub fn populate_level(ecs : &mut World) {
+    //Pick the spots for each feature first, so the map and random number generator aren't borrowed while entities are created
+    let mut braziers : Vec<(i32, i32)> = Vec::new();
+    let mut fungi : Vec<(i32, i32)> = Vec::new();
+    let mut statues : Vec<(i32, i32)> = Vec::new();
+    let mut goblins : Vec<(i32, i32)> = Vec::new();
+    let mut lurkers : Vec<(i32, i32)> = Vec::new();
+    let mut shrines : Vec<(i32, i32, ShrineEffect)> = Vec::new();
+    let mut rations : Vec<(i32, i32)> = Vec::new();
+    {
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let is_open_floor = |x : i32, y : i32| {
+            matches!(map.try_idx(x, y), Some(idx) if map.tiles[idx] == TileType::Floor) && (x, y) != (map.starting_position_x, map.starting_position_y)
+        };
+
+        //Light a third of the rooms with a brazier in their corner
+        for room in map.rooms.iter() {
+            if rng.roll_dice(1, 3) == 1 && is_open_floor(room.x1 + 1, room.y1 + 1) {
+                braziers.push((room.x1 + 1, room.y1 + 1));
+            }
+        }
+
+        //Stand a statue in the middle of some of the larger rooms
+        for room in map.rooms.iter() {
+            let (x, y) = room.center();
+            if room.x2 - room.x1 >= 8 && room.y2 - room.y1 >= 8 && rng.roll_dice(1, 4) == 1
+                && is_open_floor(x, y) && is_open_floor(x + 1, y) && is_open_floor(x, y + 1) && is_open_floor(x + 1, y + 1) {
+                statues.push((x, y));
+            }
+        }
+
+        //Scatter glowing fungi over the floor
+        for _ in 0..(map.width * map.height / 600) {
+            let x = rng.range(1, map.width - 1);
+            let y = rng.range(1, map.height - 1);
+            if is_open_floor(x, y) {
+                fungi.push((x, y));
+            }
+        }
+
+        //Place more monsters the deeper the level is, one in four of them a lurker that keeps out of sight
+        for _ in 0..(4 + map.depth) {
+            let x = rng.range(1, map.width - 1);
+            let y = rng.range(1, map.height - 1);
+            if is_open_floor(x, y) {
+                if rng.roll_dice(1, 4) == 1 { lurkers.push((x, y)); } else { goblins.push((x, y)); }
+            }
+        }
+
+        //Hide a shrine somewhere on the open floor of half of the levels
+        let open_floor : Vec<(i32, i32)> = (0..map.tiles.len()).map(|idx| map.idx_xy(idx)).filter(|(x, y)| is_open_floor(*x, *y)).collect();
+        if rng.roll_dice(1, 2) == 1 {
+            let effect = match rng.roll_dice(1, 3) {
+                1 => ShrineEffect::MagicMapping,
+                2 => ShrineEffect::Telepathy,
+                _ => ShrineEffect::Amnesia
+            };
+            if let Some((x, y)) = random_tile(&open_floor, &mut rng) {
+                shrines.push((x, y, effect));
+            }
+        }
+
+        //Leave a few rations lying around, so the player can keep their hunger at bay
+        for _ in 0..rng.roll_dice(1, 3) {
+            if let Some((x, y)) = random_tile(&open_floor, &mut rng) {
+                rations.push((x, y));
+            }
+        }
+    }
+
+    for (x, y) in braziers.iter() { brazier(ecs, *x, *y); }
+    for (x, y) in fungi.iter() { glowing_fungus(ecs, *x, *y); }
+    for (x, y) in statues.iter() { statue(ecs, *x, *y); }
+    for (x, y) in goblins.iter() { goblin(ecs, *x, *y); }
+    for (x, y) in lurkers.iter() { lurker(ecs, *x, *y); }
+    for (x, y, effect) in shrines.iter() { shrine(ecs, *x, *y, *effect); }
+    for (x, y) in rations.iter() { ration(ecs, *x, *y); }
+}
+
+//Function to pick a random tile from a list of them, if there are any
+fn random_tile(tiles : &[(i32, i32)], rng : &mut RandomNumberGenerator) -> Option<(i32, i32)> {
+    if tiles.is_empty() { return None; }
+    Some(tiles[rng.range(0, tiles.len() as i32) as usize])
+}
+
+//Function to create a brazier, which lights up the room around it
+fn brazier(ecs : &mut World, x : i32, y : i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('☼'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2
+        })
+        .with(Name{ name : "Brazier".to_string() })
+        .with(Memorable{})
+        .with(LightSource{ radius : 7, color : RGB::from_f32(1.0, 0.6, 0.25) })
+        .build();
+}
+
+//Function to create a patch of glowing fungus, which gives off a faint light
+fn glowing_fungus(ecs : &mut World, x : i32, y : i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('♣'),
+            fg: RGB::from_u8(80, 255, 170),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2
+        })
+        .with(Name{ name : "Glowing fungus".to_string() })
+        .with(Memorable{})
+        .with(LightSource{ radius : 3, color : RGB::from_f32(0.2, 0.8, 0.55) })
+        .build();
+}
+
+//Function to create a statue, which covers a 2x2 block of tiles
+fn statue(ecs : &mut World, x : i32, y : i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('▓'),
+            fg: RGB::from_u8(170, 170, 180),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2
+        })
+        .with(TileSize{ x : 2, y : 2 })
+        .with(Name{ name : "Statue".to_string() })
+        .with(Memorable{})
+        .build();
+}
+
+//Function to create a goblin
+fn goblin(ecs : &mut World, x : i32, y : i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('g'),
+            fg: RGB::named(rltk::RED),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1
+        })
+        .with(Name{ name : "Goblin".to_string() })
+        .with(Monster{})
+        .with(Viewshed::new(8))
+        .build();
+}
+
+//Function to create a lurker, which can only be noticed from close by
+fn lurker(ecs : &mut World, x : i32, y : i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('l'),
+            fg: RGB::from_u8(120, 80, 160),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1
+        })
+        .with(Name{ name : "Lurker".to_string() })
+        .with(Monster{})
+        .with(Hidden{ detection_range : 2 })
+        .with(Viewshed::with_algorithm(6, FovAlgorithm::Permissive)) //Lurkers peer round corners
+        .build();
+}
+
+//Function to create a shrine, which has an effect on the player's sight when they interact with it
+fn shrine(ecs : &mut World, x : i32, y : i32, effect : ShrineEffect) {
+    let (name, fg) = match effect {
+        ShrineEffect::MagicMapping => ("Shrine of Sight", RGB::named(rltk::CYAN)),
+        ShrineEffect::Telepathy => ("Shrine of Minds", RGB::named(rltk::MAGENTA)),
+        ShrineEffect::Amnesia => ("Shrine of Forgetting", RGB::named(rltk::GRAY))
+    };
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('∩'),
+            fg,
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2
+        })
+        .with(Name{ name : name.to_string() })
+        .with(Shrine{ effect })
+        .with(Memorable{})
+        .build();
+}
+
+//Function to create a ration, which the player can eat to stave off hunger
+fn ration(ecs : &mut World, x : i32, y : i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2
+        })
+        .with(Name{ name : "Ration".to_string() })
+        .with(ProvidesFood{})
+        .with(Memorable{})
+        .build();
+}
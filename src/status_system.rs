@@ -0,0 +1,28 @@
+use specs::prelude::*;
+use super::{Telepathy, Player, GameLog};
+
+pub struct StatusSystem {}
+
+impl<'a> System<'a> for StatusSystem {
+    type SystemData = (Entities<'a>, WriteStorage<'a, Telepathy>, ReadStorage<'a, Player>, WriteExpect<'a, GameLog>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        //Get the necessary data from the ECS
+        let (entities, mut telepathy, player, mut log) = data;
+
+        //Count down the status effects, and take off the ones that have worn off
+        let mut worn_off : Vec<Entity> = Vec::new();
+        for (entity, telepathy) in (&entities, &mut telepathy).join() {
+            telepathy.turns -= 1;
+            if telepathy.turns <= 0 {
+                worn_off.push(entity);
+            }
+        }
+        for entity in worn_off.iter() {
+            telepathy.remove(*entity);
+            if player.get(*entity).is_some() {
+                log.entries.push("You can no longer sense the minds around you.".to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use rltk::RandomNumberGenerator;
+use super::{Map, TileType, TerrainConfig};
+
+impl Map {
+    //Function to scatter rubble and lava over the floor of a generated map without disconnecting it
+    pub fn apply_terrain_features(&mut self, config : &TerrainConfig, rng : &mut RandomNumberGenerator) {
+        let start_idx = self.start_idx();
+        let area = self.width * self.height;
+
+        //Scatter patches of rubble, which is slow to cross but never blocks a path
+        for _ in 0..(config.rubble_patches * area / 10000) {
+            let mut x = rng.range(1, self.width - 1);
+            let mut y = rng.range(1, self.height - 1);
+            for _ in 0..config.rubble_patch_size { //Random walk from the patch's origin, covering the floor it passes over
+                if let Some(idx) = self.try_idx(x, y) {
+                    if self.tiles[idx] == TileType::Floor && idx != start_idx {
+                        self.tiles[idx] = TileType::Rubble;
+                    }
+                }
+                x = (x + rng.range(-1, 2)).max(1).min(self.width - 2);
+                y = (y + rng.range(-1, 2)).max(1).min(self.height - 2);
+            }
+        }
+
+        //Place single lava vents on deeper levels
+        if self.depth < config.lava_min_depth { return; }
+        for _ in 0..(config.lava_vents * area / 10000) {
+            let x = rng.range(2, self.width - 2);
+            let y = rng.range(2, self.height - 2);
+            let idx = match self.try_idx(x, y) {
+                Some(idx) if idx != start_idx && self.tiles[idx] == TileType::Floor => idx,
+                _ => continue
+            };
+
+            //Only place a vent in open ground, so the tiles around it still connect to each other
+            let open = self.neighbors(x, y).all(|(nx, ny)| matches!(self.try_idx(nx, ny), Some(neighbor_idx) if self.tiles[neighbor_idx].is_walkable()));
+            if open {
+                self.tiles[idx] = TileType::Lava;
+            }
+        }
+    }
+}
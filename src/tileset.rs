@@ -0,0 +1,68 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use super::{Map, TileType};
+
+//Path of the data file mapping tile types and entities to sprites in the tileset
+pub const TILESET_PATH : &str = "resources/tileset.json";
+
+//Name of the font holding the tileset's sprites
+pub const TILESET_FONT : &str = "dungeon_tiles.png";
+
+//Resource selecting how the map is drawn
+#[derive(PartialEq, Copy, Clone)]
+pub enum RenderMode {
+    Ascii, Tiles
+}
+
+//Resource mapping tile types and entities to sprite indices in the tileset font
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Tileset {
+    pub tiles : HashMap<TileType, rltk::FontCharType>,
+    pub walls : Vec<rltk::FontCharType>, //Sprite for each wall bitmask, indexed by the bitmask
+    pub entities : HashMap<String, rltk::FontCharType> //Sprite for each entity, looked up by name
+}
+
+impl Tileset {
+    //Function to load the tileset mapping from a data file, using an empty mapping if there is no file and stopping with a clear message if it can't be read or parsed
+    pub fn load(path : &str) -> Tileset {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Tileset::default(),
+            Err(err) => panic!("Unable to read tileset {}: {}", path, err)
+        };
+        serde_json::from_str(&data).unwrap_or_else(|err| panic!("Unable to parse tileset {}: {}", path, err))
+    }
+
+    //Function to get the sprite for the tile at a position, picking walls by which of their neighbours are also walls
+    pub fn tile_sprite(&self, map : &Map, x : i32, y : i32) -> Option<rltk::FontCharType> {
+        let idx = map.try_idx(x, y)?;
+        if map.tiles[idx] == TileType::Wall {
+            self.walls.get(map.wall_masks[idx] as usize).copied()
+        } else {
+            self.tiles.get(&map.tiles[idx]).copied()
+        }
+    }
+
+    //Function to get the sprite for an entity with the given name
+    pub fn entity_sprite(&self, name : &str) -> Option<rltk::FontCharType> {
+        self.entities.get(name).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_tileset_covers_every_tile_type() {
+        let tileset = Tileset::load(TILESET_PATH);
+        assert_eq!(tileset.walls.len(), 16, "Every wall bitmask needs a sprite");
+        for tile in [TileType::Floor, TileType::DownStairs, TileType::ShallowWater, TileType::DeepWater, TileType::Lava, TileType::Bridge, TileType::Rubble].iter() {
+            assert!(tileset.tiles.contains_key(tile), "Missing a sprite for a tile type");
+        }
+        for name in ["Player", "Brazier", "Glowing fungus", "Statue", "Goblin", "Lurker", "Ration", "Shrine of Sight", "Shrine of Minds", "Shrine of Forgetting"].iter() {
+            assert!(tileset.entity_sprite(name).is_some(), "Missing a sprite for {}", name);
+        }
+    }
+}
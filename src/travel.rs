@@ -0,0 +1,226 @@
+use specs::prelude::*;
+use rltk::{Point, BaseMap, Algorithm2D};
+use std::collections::VecDeque;
+use super::{Map, TileType, Renderable, Viewshed, Monster, ProvidesFood, Shrine, Command, GameLog};
+
+//Resource holding the route the player is automatically travelling along, one step per turn
+#[derive(Default)]
+pub struct Travel {
+    pub path : VecDeque<usize>,
+    pub exploring : bool, //Auto-explore picks each step as it goes instead of following a fixed route
+    pub seen : Vec<Entity> //Entities that were already in view when travel started, so they don't interrupt it
+}
+
+impl Travel {
+    //Function to check if the player is currently travelling
+    pub fn is_active(&self) -> bool {
+        self.exploring || !self.path.is_empty()
+    }
+
+    //Function to stop travelling
+    pub fn stop(&mut self) {
+        self.path.clear();
+        self.exploring = false;
+        self.seen.clear();
+    }
+}
+
+//Function to get all the entities other than the player that the player can see
+pub fn visible_entities(ecs : &World) -> Vec<Entity> {
+    let player_entity = ecs.fetch::<Entity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let renderables = ecs.read_storage::<Renderable>();
+
+    match viewsheds.get(*player_entity) {
+        Some(viewshed) => viewshed.visible_entities.iter().filter(|entity| renderables.get(**entity).is_some()).copied().collect(),
+        None => Vec::new()
+    }
+}
+
+//Function to check if an entity is a monster or an item, the only things worth stopping auto-explore for
+fn is_monster_or_item(ecs : &World, entity : Entity) -> bool {
+    ecs.read_storage::<Monster>().get(entity).is_some()
+        || ecs.read_storage::<ProvidesFood>().get(entity).is_some()
+        || ecs.read_storage::<Shrine>().get(entity).is_some()
+}
+
+//View of the map that only lets routes pass through tiles the player has revealed, so travelling doesn't give away the layout of the unexplored parts of the level
+struct KnownMap<'a> {
+    map : &'a Map
+}
+
+impl<'a> Algorithm2D for KnownMap<'a> {
+    //Implements the Algorithm2D trait function to return the map's dimensions
+    fn dimensions(&self) -> Point {
+        self.map.dimensions()
+    }
+}
+
+impl<'a> BaseMap for KnownMap<'a> {
+    //Implements the BaseMap trait function to check if a tile at the given index can be seen through
+    fn is_opaque(&self, idx : usize) -> bool {
+        self.map.is_opaque(idx)
+    }
+
+    //Implements the BaseMap trait function to determine the distance between two points in pathfinding
+    fn get_pathing_distance(&self, idx1 : usize, idx2 : usize) -> f32 {
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+
+    //Implements the BaseMap trait function to return the exits from a tile, leaving out the tiles the player hasn't revealed
+    fn get_available_exits(&self, idx : usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.map.get_available_exits(idx).into_iter().filter(|(exit_idx, _cost)| self.map.revealed_tiles[*exit_idx]).collect()
+    }
+}
+
+//Function to find a route between two tiles with A* over the tiles the player has revealed, not including the tile it starts on
+fn find_route(map : &Map, start_idx : usize, target_idx : usize) -> Option<VecDeque<usize>> {
+    if start_idx == target_idx || !map.revealed_tiles[target_idx] || !map.tiles[target_idx].is_walkable() { return None; }
+
+    let path = rltk::a_star_search(start_idx, target_idx, &KnownMap{ map });
+    if !path.success { return None; }
+    Some(path.steps.into_iter().skip(1).collect()) //The first step is the tile the route starts on
+}
+
+//Function to start travelling along a route
+fn set_route(ecs : &mut World, route : VecDeque<usize>) {
+    let seen = visible_entities(ecs);
+    let mut travel = ecs.write_resource::<Travel>();
+    travel.stop();
+    travel.path = route;
+    travel.seen = seen;
+}
+
+//Function to find a route from the player to the given tile with A* and start travelling along it, returning false if there is no route
+pub fn start_travel(ecs : &mut World, target_idx : usize) -> bool {
+    let route = {
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<Point>();
+        map.try_idx(player_pos.x, player_pos.y).and_then(|player_idx| find_route(&*map, player_idx, target_idx))
+    };
+
+    match route {
+        Some(route) => {
+            set_route(ecs, route);
+            true
+        }
+        None => false
+    }
+}
+
+//Function to start travelling to the nearest down stairs the player has revealed, returning false and logging why if there is none to go to
+pub fn start_travel_to_stairs(ecs : &mut World) -> bool {
+    let route = {
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<Point>();
+        let player_idx = match map.try_idx(player_pos.x, player_pos.y) {
+            Some(player_idx) => player_idx,
+            None => return false
+        };
+        let mut log = ecs.write_resource::<GameLog>();
+
+        let known_stairs : Vec<usize> = (0..map.tiles.len()).filter(|&i| map.revealed_tiles[i] && map.tiles[i] == TileType::DownStairs).collect();
+        if known_stairs.is_empty() {
+            log.entries.push("You haven't found any stairs down yet.".to_string());
+            return false;
+        }
+        if known_stairs.contains(&player_idx) {
+            log.entries.push("You are already standing on the stairs.".to_string());
+            return false;
+        }
+
+        //Take the shortest of the routes to each known staircase
+        let route = known_stairs.iter()
+            .filter_map(|&stairs_idx| find_route(&*map, player_idx, stairs_idx))
+            .min_by_key(|route| route.len());
+        if route.is_none() {
+            log.entries.push("You can't find a way to the stairs.".to_string());
+        }
+        route
+    };
+
+    match route {
+        Some(route) => {
+            set_route(ecs, route);
+            true
+        }
+        None => false
+    }
+}
+
+//Function to start exploring the level automatically
+pub fn start_explore(ecs : &mut World) {
+    let seen = visible_entities(ecs);
+    let mut travel = ecs.write_resource::<Travel>();
+    travel.stop();
+    travel.exploring = true;
+    travel.seen = seen;
+}
+
+//Function to get the frontier of the explored part of the level: every revealed tile that can be walked on next to a tile that hasn't been revealed yet
+fn explore_frontier(map : &Map) -> Vec<usize> {
+    let mut frontier : Vec<usize> = Vec::new();
+    for idx in 0..map.tiles.len() {
+        if !map.revealed_tiles[idx] || !map.tiles[idx].is_walkable() { continue; }
+        let (x, y) = map.idx_xy(idx);
+        if map.neighbors(x, y).any(|(nx, ny)| matches!(map.try_idx(nx, ny), Some(neighbor_idx) if !map.revealed_tiles[neighbor_idx])) {
+            frontier.push(idx);
+        }
+    }
+    frontier
+}
+
+//Function to find the next step towards the nearest part of the frontier that can be reached, if there is one
+fn next_explore_step(map : &Map, frontier : &[usize], player_idx : usize) -> Option<usize> {
+    if frontier.is_empty() { return None; } //The level is fully explored
+
+    //Use a dijkstra map from the whole frontier over the revealed tiles to walk downhill towards whichever part of it is nearest
+    let known_map = KnownMap{ map };
+    let max_depth = (map.width * map.height) as f32;
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, frontier, &known_map, max_depth);
+    match dijkstra_map.map[player_idx] {
+        distance if distance == std::f32::MAX => None, //None of the frontier can be reached
+        distance if distance == 0.0 => None, //The player is on the frontier and still can't see what's next to them
+        _ => rltk::DijkstraMap::find_lowest_exit(&dijkstra_map, player_idx, &known_map)
+    }
+}
+
+//Function to get the command for the next step of the player's travel, stopping if something new has come into view
+pub fn next_travel_command(ecs : &mut World) -> Option<Command> {
+    if !ecs.fetch::<Travel>().is_active() { return None; }
+
+    //Stop if anything the player hadn't seen when they set off is now in view; auto-explore only stops for monsters and items, so it doesn't halt at every piece of scenery
+    let visible = visible_entities(ecs);
+    let mut travel = ecs.write_resource::<Travel>();
+    if visible.iter().any(|entity| !travel.seen.contains(entity) && (!travel.exploring || is_monster_or_item(ecs, *entity))) {
+        travel.stop();
+        return None;
+    }
+
+    //Step towards the next tile on the route, stopping if the player has been knocked off it or there is nowhere left to explore
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+    let next_step = if travel.exploring {
+        let frontier = explore_frontier(&*map);
+        if frontier.is_empty() {
+            ecs.write_resource::<GameLog>().entries.push("Nothing left to explore.".to_string());
+        }
+        map.try_idx(player_pos.x, player_pos.y).and_then(|player_idx| next_explore_step(&*map, &frontier, player_idx))
+    } else {
+        travel.path.pop_front()
+    };
+    let next_idx = match next_step {
+        Some(next_idx) => next_idx,
+        None => {
+            travel.stop();
+            return None;
+        }
+    };
+    let (next_x, next_y) = map.idx_xy(next_idx);
+    let (dx, dy) = (next_x - player_pos.x, next_y - player_pos.y);
+    if dx.abs() > 1 || dy.abs() > 1 {
+        travel.stop();
+        return None;
+    }
+    Some(Command::Move{ dx, dy })
+}
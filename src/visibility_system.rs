@@ -1,35 +1,115 @@
 use specs::prelude::*;
-use super::{Viewshed, Position, Map, Player};
-use rltk::{field_of_view, Point};
+use super::{Viewshed, Position, Map, Player, Hidden, TileSize, Monster, Telepathy, ParticleLifetime, fov};
+use rltk::Point;
 
 pub struct VisibilitySystem {}
 
 impl<'a> System<'a> for VisibilitySystem {
-    type SystemData = (WriteExpect<'a, Map>, Entities<'a>, WriteStorage<'a, Viewshed>, WriteStorage<'a, Position>, ReadStorage<'a, Player>);
+    type SystemData = (WriteExpect<'a, Map>, Entities<'a>, WriteStorage<'a, Viewshed>, WriteStorage<'a, Position>, ReadStorage<'a, Player>,
+                       ReadStorage<'a, Hidden>, ReadStorage<'a, TileSize>, ReadStorage<'a, Monster>, ReadStorage<'a, Telepathy>, ReadStorage<'a, ParticleLifetime>);
 
     fn run(&mut self, data : Self::SystemData) {
         //Get the necessary data from the ECS
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, hidden, sizes, monsters, telepathy, particles) = data;
 
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() { //All entities with a viewshed and a position
             if viewshed.dirty { //Viewshed needs to be updated
                 viewshed.dirty = false; //Mark viewshed as up to date
-                viewshed.visible_tiles.clear(); //Clear the list of visible tiles
-                viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map); //Use RLTK's fov function to calculate the visibility of the tiles in a given map from a given position within a given range
-                viewshed.visible_tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height); //Retain only tiles within the bounds of the map
+                let is_player = player.get(ent).is_some(); //Check if the entity has a Player component
 
-                //If the current entity is the player, reveal the tiles they can see
-                let _p : Option<&Player> = player.get(ent); //Check if the entity has a Player component
-                if let Some(_p) = _p { //If there was a Playe component
-                    for t in map.visible_tiles.iter_mut() { *t = false }; //Mark all tiles in the map not visible
-                    //Mark all tiles on the map that the player can see as visible
+                //If the current entity is the player, mark the tiles they could see before as not visible, leaving the rest of the map alone
+                if is_player {
                     for vis in viewshed.visible_tiles.iter() {
-                        let idx = map.xy_idx(vis.x, vis.y);
-                        map.revealed_tiles[idx] = true;
-                        map.visible_tiles[idx] = true;
+                        if let Some(idx) = map.try_idx(vis.x, vis.y) { //The tiles may be from the last map, if the player has just changed level
+                            map.visible_tiles.remove(idx);
+                        }
+                    }
+                }
+
+                //Calculate the tiles that can be seen from the entity's position within its range, reusing the viewshed's buffers
+                fov::update_viewshed(viewshed, Point::new(pos.x, pos.y), &*map);
+
+                //If the current entity is the player, reveal the tiles they can see
+                if is_player {
+                    //Mark all tiles on the map that the player can see as visible, as long as there is light to see them by
+                    for idx in viewshed.visible.iter() {
+                        if map.is_lit(idx) {
+                            map.reveal_tile(idx);
+                            map.visible_tiles.insert(idx);
+                        }
                     }
                 }
             }
         }
+
+        //Work out which entities every viewer can see, every turn, as entities move whether or not the viewer does, leaving out particles as they're only effects
+        let targets : Vec<Target> = (&entities, &pos, hidden.maybe(), sizes.maybe(), monsters.maybe(), !&particles).join()
+            .map(|(ent, pos, hidden, size, monster, _)| {
+                let (width, height) = size.map(|size| (size.x, size.y)).unwrap_or((1, 1));
+                let mut covered : Vec<usize> = Vec::new();
+                for y in pos.y .. pos.y + height {
+                    for x in pos.x .. pos.x + width {
+                        if let Some(idx) = map.try_idx(x, y) {
+                            covered.push(idx);
+                        }
+                    }
+                }
+                Target{ entity : ent, pos : Point::new(pos.x, pos.y), covered, detection_range : hidden.map(|hidden| hidden.detection_range), is_monster : monster.is_some() }
+            })
+            .collect();
+
+        for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
+            //The player can only see lit tiles, everything else sees every tile in its viewshed
+            let visible = if player.get(ent).is_some() { &map.visible_tiles } else { &viewshed.visible };
+
+            let viewer_pos = Point::new(pos.x, pos.y);
+            let distance = |target : &Target| rltk::DistanceAlg::Pythagoras.distance2d(viewer_pos, target.pos);
+            viewshed.visible_entities.clear();
+            viewshed.visible_entities.extend(targets.iter()
+                .filter(|target| target.entity != ent && target.covered.iter().any(|idx| visible.contains(*idx)))
+                .filter(|target| match target.detection_range { //Hidden entities have to be close to be noticed
+                    Some(detection_range) => distance(target) <= detection_range as f32,
+                    None => true
+                })
+                .map(|target| target.entity));
+
+            //Telepathy senses every monster in range that can't already be seen, wherever it is
+            viewshed.telepathic_entities.clear();
+            if let Some(telepathy) = telepathy.get(ent) {
+                let visible_entities = &viewshed.visible_entities;
+                viewshed.telepathic_entities.extend(targets.iter()
+                    .filter(|target| target.is_monster && target.entity != ent && !visible_entities.contains(&target.entity))
+                    .filter(|target| distance(target) <= telepathy.range as f32)
+                    .map(|target| target.entity));
+            }
+        }
+    }
+}
+
+//An entity that viewers might be able to see
+struct Target {
+    entity : Entity,
+    pos : Point,
+    covered : Vec<usize>, //Indices of the tiles the entity covers
+    detection_range : Option<i32>, //How close a viewer has to be to notice the entity, if it is hidden
+    is_monster : bool
+}
+
+//Function to check if one entity can currently see another
+pub fn can_see(ecs : &World, viewer : Entity, target : Entity) -> bool {
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    match viewsheds.get(viewer) {
+        Some(viewshed) => viewshed.visible_entities.contains(&target),
+        None => false
     }
 }
+
+//Function to get every entity that can currently see the target
+pub fn seen_by(ecs : &World, target : Entity) -> Vec<Entity> {
+    let entities = ecs.entities();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    (&entities, &viewsheds).join()
+        .filter(|(_viewer, viewshed)| viewshed.visible_entities.contains(&target))
+        .map(|(viewer, _viewshed)| viewer)
+        .collect()
+}
@@ -0,0 +1,216 @@
+use rltk::RandomNumberGenerator;
+use super::{Map, TileType, WaterConfig};
+use std::collections::VecDeque;
+
+//Most attempts at reconnecting cut off tiles, after which any still cut off are walled off
+const MAX_BRIDGE_ATTEMPTS : i32 = 200;
+
+impl Map {
+    //Function to carve a river and/or lakes into a generated map, then make sure everything reachable before still is
+    pub fn apply_water_features(&mut self, config : &WaterConfig, rng : &mut RandomNumberGenerator) {
+        let start_idx = self.start_idx();
+        let distances_before = self.distances_from(start_idx); //Remember which tiles could be reached before any water was added
+        let original_tiles = self.tiles.clone();
+
+        if rng.roll_dice(1, 100) <= config.river_chance {
+            self.carve_river(config, &original_tiles, start_idx, rng);
+        }
+        if rng.roll_dice(1, 100) <= config.lake_chance {
+            for _ in 0..rng.range(1, config.max_lakes + 1) {
+                self.carve_lake(config, start_idx, rng);
+            }
+        }
+
+        self.restore_connectivity(start_idx, &distances_before);
+    }
+
+    //Function to carve a winding river from one edge of the map to the opposite one
+    fn carve_river(&mut self, config : &WaterConfig, original_tiles : &[TileType], start_idx : usize, rng : &mut RandomNumberGenerator) {
+        let vertical = rng.range(0, 2) == 1; //50% chance of the river flowing north to south or west to east
+        let (length, breadth) = if vertical { (self.height, self.width) } else { (self.width, self.height) };
+        let mut offset = rng.range(breadth / 4, breadth * 3 / 4); //Position of the river's center across the direction it flows
+
+        for along in 1..length - 1 {
+            for across in offset - config.river_bank_width ..= offset + config.river_bank_width {
+                let (x, y) = if vertical { (across, along) } else { (along, across) };
+                if across == offset {
+                    //Bridge the river where it crosses a corridor, otherwise make it deep
+                    if self.is_corridor(original_tiles, x, y) {
+                        self.set_water_tile(x, y, TileType::Bridge, start_idx);
+                    } else {
+                        self.set_water_tile(x, y, TileType::DeepWater, start_idx);
+                    }
+                } else {
+                    self.set_water_tile(x, y, TileType::ShallowWater, start_idx);
+                }
+            }
+
+            //Let the river wander
+            offset = (offset + rng.range(-1, 2)).max(config.river_bank_width + 1).min(breadth - config.river_bank_width - 2);
+        }
+    }
+
+    //Function to carve a roughly circular lake around a random floor tile
+    fn carve_lake(&mut self, config : &WaterConfig, start_idx : usize, rng : &mut RandomNumberGenerator) {
+        let radius = rng.range(config.lake_min_radius, config.lake_max_radius + 1);
+        let center_x = rng.range(1, self.width - 1);
+        let center_y = rng.range(1, self.height - 1);
+        if !matches!(self.try_idx(center_x, center_y), Some(idx) if self.tiles[idx] == TileType::Floor) { return; } //Only start lakes in open areas
+
+        for y in center_y - radius ..= center_y + radius {
+            for x in center_x - radius ..= center_x + radius {
+                //Roughen the lake's edge by adding some noise to the distance from its center
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(rltk::Point::new(center_x, center_y), rltk::Point::new(x, y)) + rng.range(0, 2) as f32 * 0.5;
+                if distance < radius as f32 - 1.0 {
+                    self.set_water_tile(x, y, TileType::DeepWater, start_idx);
+                } else if distance < radius as f32 {
+                    self.set_water_tile(x, y, TileType::ShallowWater, start_idx);
+                }
+            }
+        }
+    }
+
+    //Function to turn a tile into water, leaving the map's borders, the start position, special tiles and solid rock alone, so the banks never open new passages through the layout
+    fn set_water_tile(&mut self, x : i32, y : i32, water : TileType, start_idx : usize) {
+        if x < 1 || x > self.width - 2 || y < 1 || y > self.height - 2 { return; }
+        let idx = match self.try_idx(x, y) {
+            Some(idx) if idx != start_idx => idx,
+            _ => return
+        };
+
+        match self.tiles[idx] {
+            TileType::Floor | TileType::Rubble => self.tiles[idx] = water,
+            TileType::ShallowWater if water != TileType::ShallowWater => self.tiles[idx] = water, //Deep water and bridges take priority over the banks
+            _ => {}
+        }
+    }
+
+    //Function to check if a position was part of a corridor before any water was added
+    fn is_corridor(&self, original_tiles : &[TileType], x : i32, y : i32) -> bool {
+        match self.try_idx(x, y) {
+            Some(idx) if original_tiles[idx].is_walkable() => {}
+            _ => return false
+        }
+
+        if !self.rooms.is_empty() {
+            //On maps made of rooms, any open tile outside of a room is a corridor
+            !self.rooms.iter().any(|room| x > room.x1 && x <= room.x2 && y > room.y1 && y <= room.y2)
+        } else {
+            //Otherwise look for a narrow passage with walls on both sides
+            let wall = |x : i32, y : i32| match self.try_idx(x, y) {
+                Some(idx) => original_tiles[idx] == TileType::Wall,
+                None => true //The edge of the map walls a passage in as well as a wall does
+            };
+            (wall(x - 1, y) && wall(x + 1, y)) || (wall(x, y - 1) && wall(x, y + 1))
+        }
+    }
+
+    //Function to bridge any water that cut off tiles that used to be reachable, then wall off new tiles that can't be reached
+    fn restore_connectivity(&mut self, start_idx : usize, distances_before : &[f32]) {
+        let mut last_target : Option<usize> = None;
+        for _ in 0..MAX_BRIDGE_ATTEMPTS {
+            //Find a tile that could be reached before the water was added but can't be reached now
+            let distances = self.distances_from(start_idx);
+            let cut_off = (0..self.tiles.len()).find(|&i| {
+                distances_before[i] != std::f32::MAX && self.tiles[i].is_walkable() && distances[i] == std::f32::MAX
+            });
+
+            match cut_off {
+                Some(target_idx) => {
+                    //Wall the tile off if there is no route to bridge, or bridging the last time didn't reconnect it, so every attempt makes progress
+                    if last_target == Some(target_idx) || !self.bridge_path(start_idx, target_idx) {
+                        self.tiles[target_idx] = TileType::Wall;
+                    }
+                    last_target = Some(target_idx);
+                }
+                None => break
+            }
+        }
+
+        //Use the dijkstra map again to fill in any tiles we cannot reach from the starting point, so no cut off pocket is left behind
+        let distances = self.distances_from(start_idx);
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            if tile.is_walkable() && distances[i] == std::f32::MAX {
+                *tile = TileType::Wall;
+            }
+        }
+    }
+
+    //Function to find the route between two tiles that crosses the least deep water and turn that water into bridges, returning false if there is no route
+    fn bridge_path(&mut self, start_idx : usize, target_idx : usize) -> bool {
+        //0-1 breadth first search, where walkable tiles are free to cross and deep water costs one bridge tile
+        let mut cost = vec![std::i32::MAX; self.tiles.len()];
+        let mut came_from = vec![usize::MAX; self.tiles.len()];
+        let mut open_list = VecDeque::new();
+        cost[start_idx] = 0;
+        open_list.push_back(start_idx);
+
+        while let Some(idx) = open_list.pop_front() {
+            if idx == target_idx { break; }
+            let (x, y) = self.idx_xy(idx);
+            for ny in y - 1 ..= y + 1 {
+                for nx in x - 1 ..= x + 1 {
+                    if nx < 1 || nx > self.width - 2 || ny < 1 || ny > self.height - 2 { continue; }
+                    let next_idx = match self.try_idx(nx, ny) {
+                        Some(next_idx) => next_idx,
+                        None => continue
+                    };
+                    let step_cost = match self.tiles[next_idx] {
+                        TileType::DeepWater => 1,
+                        tile if tile.is_walkable() => 0,
+                        _ => continue
+                    };
+                    if cost[idx] + step_cost < cost[next_idx] {
+                        cost[next_idx] = cost[idx] + step_cost;
+                        came_from[next_idx] = idx;
+                        if step_cost == 0 { open_list.push_front(next_idx); } else { open_list.push_back(next_idx); }
+                    }
+                }
+            }
+        }
+
+        //Walk back along the route, bridging the deep water on it
+        let mut idx = target_idx;
+        while idx != start_idx && came_from[idx] != usize::MAX {
+            if self.tiles[idx] == TileType::DeepWater {
+                self.tiles[idx] = TileType::Bridge;
+            }
+            idx = came_from[idx];
+        }
+        came_from[target_idx] != usize::MAX
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rltk::RandomNumberGenerator;
+    use crate::{Map, MapConfig, TileType};
+
+    #[test]
+    fn water_keeps_every_walkable_tile_and_the_stairs_reachable() {
+        //Always carve both a river and lakes, through both kinds of level
+        let mut config = MapConfig::default();
+        config.water.river_chance = 100;
+        config.water.lake_chance = 100;
+
+        let (mut maps, mut maps_with_water) = (0, 0);
+        for cellular_automata_chance in [0, 100].iter() {
+            config.cellular_automata_chance = *cellular_automata_chance;
+            for seed in 1..=5 {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                for depth in 1..=config.final_depth {
+                    let map = Map::new_map_for_depth(depth, &config, &mut rng);
+                    let distances = map.distances_from(map.start_idx());
+                    let unreachable = (0..map.tiles.len()).find(|idx| map.tiles[*idx].is_walkable() && distances[*idx] == std::f32::MAX);
+                    assert_eq!(unreachable.map(|idx| map.idx_xy(idx)), None, "Walkable tile cut off (seed {}, depth {})", seed, depth);
+                    assert!(map.tiles.contains(&TileType::DownStairs), "No stairs (seed {}, depth {})", seed, depth);
+                    maps += 1;
+                    if map.tiles.iter().any(|tile| *tile == TileType::ShallowWater || *tile == TileType::DeepWater) { maps_with_water += 1; }
+                }
+            }
+        }
+
+        //Water can miss the open ground of a level entirely, but most levels should have had some to check
+        assert!(maps_with_water * 2 > maps, "Only {} of {} levels had water", maps_with_water, maps);
+    }
+}